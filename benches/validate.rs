@@ -0,0 +1,38 @@
+use std::{fs, path::Path};
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion};
+use slp_validate::{Config, Parser};
+
+fn load_corpus() -> Vec<Bytes> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_replays");
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "slp"))
+        .collect();
+    files.sort();
+
+    files
+        .iter()
+        .map(|path| Bytes::from(fs::read(path).unwrap()))
+        .collect()
+}
+
+fn validate_corpus(c: &mut Criterion) {
+    let corpus = load_corpus();
+    let config = Config::default();
+    let mut parser = Parser::new();
+
+    c.bench_function("validate test_replays", |b| {
+        b.iter(|| {
+            for replay in &corpus {
+                // errors are expected for some fixtures, only the time taken matters here
+                let _ = parser.validate_bytes(replay.clone(), &config);
+            }
+        })
+    });
+}
+
+criterion_group!(benches, validate_corpus);
+criterion_main!(benches);
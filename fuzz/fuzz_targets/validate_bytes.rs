@@ -0,0 +1,10 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use slp_validate::{validate_bytes, Config};
+
+// validate_bytes must return Err rather than panicking, no matter the input
+fuzz_target!(|data: &[u8]| {
+    let _ = validate_bytes(Bytes::copy_from_slice(data), &Config::default());
+});
@@ -0,0 +1,136 @@
+use ssbm_utils::prelude::Port;
+
+use crate::{
+    combo::COMBO_RESET_FRAMES,
+    game::Game,
+    ledge::is_ledge_state,
+    postframe::{flags, PostFrame},
+    stats::{is_death_state, is_tech_state},
+};
+
+/// Knocked down after a missed tech, including the getup options out of it (`DownBoundU` through
+/// `DownSpotD`)
+pub fn is_knockdown_state(action_state: u16) -> bool {
+    (183..=198).contains(&action_state)
+}
+
+/// Which player, if either, has the upper hand on a given frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Phase {
+    #[default]
+    Neutral,
+    /// The given port's opponent is in a vulnerable position
+    Advantage(Port),
+}
+
+/// Number of in-game frames a port spent in each phase, see [`Game::advantage_breakdown`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AdvantageStats {
+    pub neutral: u32,
+    pub advantage: u32,
+    pub disadvantage: u32,
+}
+
+impl AdvantageStats {
+    pub fn total(&self) -> u32 {
+        self.neutral + self.advantage + self.disadvantage
+    }
+
+    /// Fraction (0.0-1.0) of frames spent in neutral, `None` if no frames were counted
+    pub fn neutral_rate(&self) -> Option<f32> {
+        self.rate(self.neutral)
+    }
+
+    pub fn advantage_rate(&self) -> Option<f32> {
+        self.rate(self.advantage)
+    }
+
+    pub fn disadvantage_rate(&self) -> Option<f32> {
+        self.rate(self.disadvantage)
+    }
+
+    fn rate(&self, frames: u32) -> Option<f32> {
+        let total = self.total();
+        (total > 0).then(|| frames as f32 / total as f32)
+    }
+}
+
+/// True if the player can't act freely: in hitstun, knocked down, teching, or on the ledge
+fn is_vulnerable(frame: &PostFrame) -> bool {
+    frame.has_flag(flags::HITSTUN)
+        || is_knockdown_state(frame.action_state)
+        || is_tech_state(frame.action_state)
+        || is_ledge_state(frame.action_state)
+}
+
+impl Game {
+    /// The phase of every in-game frame, in frame order. A player gains advantage when their opponent becomes
+    /// vulnerable (in hitstun, knocked down, teching, or on the ledge) and keeps it until the opponent has been
+    /// out of a vulnerable state for [`COMBO_RESET_FRAMES`], is hit back, or dies. Only defined for games with exactly 2 active ports, frames
+    /// where either player is missing or dead are skipped.
+    pub fn phases(&self) -> Vec<(i32, Phase)> {
+        let ports: Vec<Port> = self.active_ports().collect();
+        let [a, b] = ports[..] else {
+            return Vec::new();
+        };
+        let (ta, tb) = (self.post_frames_indexed(a), self.post_frames_indexed(b));
+
+        let mut phases = Vec::new();
+        let mut phase = Phase::Neutral;
+        let mut since_vulnerable = 0;
+        for (fa, fb) in ta.iter().zip(tb.iter()) {
+            let (Some(fa), Some(fb)) = (fa, fb) else {
+                continue;
+            };
+            if is_death_state(fa.action_state) || is_death_state(fb.action_state) {
+                phase = Phase::Neutral;
+                continue;
+            }
+            if fa.frame_index < 0 {
+                continue;
+            }
+
+            phase = match (is_vulnerable(fa), is_vulnerable(fb)) {
+                (true, false) => {
+                    since_vulnerable = 0;
+                    Phase::Advantage(b)
+                }
+                (false, true) => {
+                    since_vulnerable = 0;
+                    Phase::Advantage(a)
+                }
+                // trades put both players back on even footing
+                (true, true) => Phase::Neutral,
+                (false, false) => {
+                    since_vulnerable += 1;
+                    if since_vulnerable > COMBO_RESET_FRAMES {
+                        Phase::Neutral
+                    } else {
+                        phase
+                    }
+                }
+            };
+            phases.push((fa.frame_index, phase));
+        }
+
+        phases
+    }
+
+    /// Number of frames the given port spent in neutral, advantage, and disadvantage, see [`Game::phases`].
+    /// All zeroes unless the game has exactly 2 active ports.
+    pub fn advantage_breakdown(&self, port: Port) -> AdvantageStats {
+        let mut stats = AdvantageStats::default();
+        if !self.active_ports().any(|p| p == port) {
+            return stats;
+        }
+        for (_, phase) in self.phases() {
+            match phase {
+                Phase::Neutral => stats.neutral += 1,
+                Phase::Advantage(p) if p == port => stats.advantage += 1,
+                Phase::Advantage(_) => stats.disadvantage += 1,
+            }
+        }
+
+        stats
+    }
+}
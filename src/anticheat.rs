@@ -0,0 +1,136 @@
+//! Classification of action states that can't be reached in vanilla Melee.
+//!
+//! `PostFrame::validate` warns about any state ID that doesn't resolve for the player's character. That alone
+//! can't tell a modded or tampered replay apart from a state ID that simply isn't mapped yet, so this module
+//! sorts those IDs by how certain it is that they're impossible.
+
+use std::collections::BTreeMap;
+
+use ssbm_utils::prelude::*;
+use ssbm_utils::types::*;
+
+use crate::game::Game;
+
+/// Number of internal character IDs, bosses and wireframes included
+const INTERNAL_CHARACTERS: u8 = 0x21;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateAnomalyKind {
+    /// A special state that belongs to a different character, e.g. a Fox entering one of Marth's specials, or
+    /// anyone entering a boss-only state. Characters can't do this in vanilla Melee.
+    ForeignState(Character),
+    /// Not a state for any character. Either tampered with or a state that hasn't been mapped yet.
+    Unrecognized,
+}
+
+impl StateAnomalyKind {
+    /// How likely (0.0-1.0) this kind of anomaly is to come from a modified game rather than a gap in the
+    /// state tables
+    pub fn confidence(&self) -> f32 {
+        match self {
+            StateAnomalyKind::ForeignState(_) => 0.9,
+            StateAnomalyKind::Unrecognized => 0.3,
+        }
+    }
+}
+
+/// Every frame on which one port (or its Nana) was in one anomalous state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StateAnomaly {
+    pub port: Port,
+    pub nana: bool,
+    pub state_id: u16,
+    pub kind: StateAnomalyKind,
+    pub first_frame: i32,
+    /// Number of post-frames in this state, rollback re-simulations included
+    pub frames: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AntiCheatReport {
+    /// Ordered by port, then by state ID
+    pub anomalies: Vec<StateAnomaly>,
+}
+
+impl AntiCheatReport {
+    /// The highest confidence of any anomaly, 0.0 for a clean replay
+    pub fn confidence(&self) -> f32 {
+        self.anomalies
+            .iter()
+            .map(|a| a.kind.confidence())
+            .fold(0.0, f32::max)
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.anomalies.is_empty()
+    }
+}
+
+/// Classifies a state ID the given character couldn't resolve. `None` if the state is valid for them.
+pub fn classify_state(state_id: u16, character: Character) -> Option<StateAnomalyKind> {
+    let resolves = |c: Character| {
+        !matches!(
+            State::from_state_and_char(state_id, Some(c)),
+            State::Unknown(_)
+        )
+    };
+
+    // Zelda and Sheik share a port and transform into each other mid-game
+    let own: &[Character] = match character {
+        Character::Zelda | Character::Sheik => &[Character::Zelda, Character::Sheik],
+        _ => &[character],
+    };
+    if own.iter().any(|c| resolves(*c)) {
+        return None;
+    }
+
+    let owner = (0..INTERNAL_CHARACTERS)
+        .filter_map(|id| Character::try_from_internal(id).ok())
+        .find(|c| resolves(*c));
+
+    Some(match owner {
+        Some(owner) => StateAnomalyKind::ForeignState(owner),
+        None => StateAnomalyKind::Unrecognized,
+    })
+}
+
+impl Game {
+    /// Every action state in the replay that isn't valid for the character in it, see [`classify_state`]
+    pub fn anticheat_report(&self) -> AntiCheatReport {
+        let mut found: BTreeMap<(u8, bool, u16), StateAnomaly> = BTreeMap::new();
+
+        for frame in &self.post_frames {
+            let (Ok(character), Some(port)) = (
+                Character::try_from_internal(frame.character),
+                Port::from_repr(frame.port),
+            ) else {
+                // already reported by PostFrame::validate
+                continue;
+            };
+            let key = (frame.port, frame.nana, frame.action_state);
+            if let Some(anomaly) = found.get_mut(&key) {
+                anomaly.frames += 1;
+                anomaly.first_frame = anomaly.first_frame.min(frame.frame_index);
+                continue;
+            }
+            let Some(kind) = classify_state(frame.action_state, character) else {
+                continue;
+            };
+            found.insert(
+                key,
+                StateAnomaly {
+                    port,
+                    nana: frame.nana,
+                    state_id: frame.action_state,
+                    kind,
+                    first_frame: frame.frame_index,
+                    frames: 1,
+                },
+            );
+        }
+
+        AntiCheatReport {
+            anomalies: found.into_values().collect(),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Many parsed games bundled into a single zstd-compressed file, for distributing a replay corpus without
+//! shipping every `.slp`.
+//!
+//! Each game is compressed separately and the index of games is stored at the end of the file, so
+//! individual games can be loaded without decompressing the rest. Games are stored the same way as
+//! [`crate::cache`] stores them, and archives are versioned with [`CACHE_VERSION`] as well.
+//!
+//! Layout: `SLPZ`, the u32 cache version, every compressed game back to back, the compressed index, and the
+//! u64 offset of the index. Integers are little endian.
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use anyhow::{ensure, Result};
+use bytes::Bytes;
+
+use crate::{
+    cache::CACHE_VERSION,
+    config::Config,
+    game::Game,
+    parse::Parser,
+    sink::{error, info},
+    utils::ParseError,
+};
+
+const ARCHIVE_MAGIC: [u8; 4] = *b"SLPZ";
+
+/// zstd compression level used when writing archives
+pub const COMPRESSION_LEVEL: i32 = 19;
+
+/// Where a single game is stored within an archive
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArchiveEntry {
+    /// `GameStart::match_id`, empty for offline games
+    pub match_id: String,
+    /// `GameStart::game_number`, distinguishes the games of a netplay set that share a match ID
+    pub game_number: Option<u32>,
+    /// Offset of the compressed game from the start of the file
+    pub offset: u64,
+    /// Compressed length in bytes
+    pub len: u64,
+}
+
+/// Writes games to a new archive. Nothing is readable until [`ArchiveWriter::finish`] writes the index.
+pub struct ArchiveWriter {
+    out: BufWriter<File>,
+    pos: u64,
+    index: Vec<ArchiveEntry>,
+}
+
+impl ArchiveWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&ARCHIVE_MAGIC)?;
+        out.write_all(&CACHE_VERSION.to_le_bytes())?;
+
+        Ok(Self {
+            out,
+            pos: (ARCHIVE_MAGIC.len() + 4) as u64,
+            index: Vec::new(),
+        })
+    }
+
+    pub fn add(&mut self, game: &Game) -> Result<()> {
+        let compressed = zstd::encode_all(&bincode::serialize(game)?[..], COMPRESSION_LEVEL)?;
+        self.out.write_all(&compressed)?;
+        self.index.push(ArchiveEntry {
+            match_id: game.start.match_id.clone(),
+            game_number: game.start.game_number,
+            offset: self.pos,
+            len: compressed.len() as u64,
+        });
+        self.pos += compressed.len() as u64;
+
+        Ok(())
+    }
+
+    /// Writes the index and flushes the file. Returns the number of games in the archive.
+    pub fn finish(mut self) -> Result<usize> {
+        let index = zstd::encode_all(&bincode::serialize(&self.index)?[..], COMPRESSION_LEVEL)?;
+        self.out.write_all(&index)?;
+        self.out.write_all(&self.pos.to_le_bytes())?;
+        self.out.flush()?;
+
+        Ok(self.index.len())
+    }
+}
+
+/// An archive opened for reading. Only the index is read up front, games are decompressed on request.
+#[derive(Debug)]
+pub struct Archive {
+    file: File,
+    index: Vec<ArchiveEntry>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = File::open(path)?;
+
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+        ensure!(
+            header[..4] == ARCHIVE_MAGIC,
+            ParseError::Value(
+                format!("archive magic {ARCHIVE_MAGIC:?}"),
+                format!("{:?}", &header[..4])
+            )
+        );
+        let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+        ensure!(
+            version == CACHE_VERSION,
+            ParseError::Value(
+                format!("cache version {CACHE_VERSION}"),
+                version.to_string()
+            )
+        );
+
+        let end = file.seek(SeekFrom::End(-8))?;
+        let mut index_offset = [0; 8];
+        file.read_exact(&mut index_offset)?;
+        let index_offset = u64::from_le_bytes(index_offset);
+        ensure!(
+            (8..=end).contains(&index_offset),
+            ParseError::Value(
+                format!("index offset at most {end}"),
+                index_offset.to_string()
+            )
+        );
+
+        file.seek(SeekFrom::Start(index_offset))?;
+        let mut index = vec![0; (end - index_offset) as usize];
+        file.read_exact(&mut index)?;
+        let index = bincode::deserialize(&zstd::decode_all(&index[..])?)?;
+
+        Ok(Self { file, index })
+    }
+
+    /// Every game in the archive, in the order they were added
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.index
+    }
+
+    /// Every game with the given match ID, ordered by game number
+    pub fn find(&self, match_id: &str) -> Vec<&ArchiveEntry> {
+        let mut found: Vec<_> = self
+            .index
+            .iter()
+            .filter(|e| e.match_id == match_id)
+            .collect();
+        found.sort_by_key(|e| e.game_number);
+
+        found
+    }
+
+    /// Decompresses a single game
+    pub fn load(&self, entry: &ArchiveEntry) -> Result<Game> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut compressed = vec![0; entry.len as usize];
+        file.read_exact(&mut compressed)?;
+
+        Ok(bincode::deserialize(&zstd::decode_all(&compressed[..])?)?)
+    }
+}
+
+/// Parses every `.slp` file in `dir` and writes the ones that parse into a new archive at `out`. Replays
+/// that fail to parse are reported and left out. Returns the number of games written.
+pub fn export_directory(dir: &Path, out: &Path, config: &Config) -> Result<usize> {
+    let mut files: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "slp"))
+        .collect();
+    files.sort();
+
+    let mut parser = Parser::new();
+    let mut writer = ArchiveWriter::create(out)?;
+    for file in files {
+        info!("Exporting {}", file.display());
+        let game = fs::read(&file)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| parser.validate_bytes(Bytes::from(data), config));
+        match game {
+            Ok(game) => writer.add(&game)?,
+            Err(e) => error!("Error exporting {}: {e}", file.display()),
+        }
+    }
+
+    writer.finish()
+}
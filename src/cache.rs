@@ -0,0 +1,73 @@
+//! Binary cache of fully parsed games, so large replay sets only need to be parsed and validated once.
+//!
+//! The format is `bincode` and is only meant to be read back by the same version of this crate. Each cache
+//! starts with [`CACHE_VERSION`], caches written by a different version are rejected rather than misread.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::{ensure, Result};
+
+use crate::{game::Game, utils::ParseError};
+
+/// Bumped whenever any type stored in a [`Game`] changes shape
+pub const CACHE_VERSION: u32 = 3;
+
+impl Game {
+    pub fn save_cache(&self, path: &Path) -> Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&CACHE_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut out, self)?;
+        out.flush()?;
+
+        Ok(())
+    }
+
+    pub fn load_cache(path: &Path) -> Result<Game> {
+        let mut input = BufReader::new(File::open(path)?);
+        let mut version = [0; 4];
+        input.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        ensure!(
+            version == CACHE_VERSION,
+            ParseError::Value(
+                format!("cache version {CACHE_VERSION}"),
+                version.to_string()
+            )
+        );
+
+        Ok(bincode::deserialize_from(input)?)
+    }
+}
+
+/// `Bytes` has no serde support, store it as a plain byte vector
+pub(crate) mod bytes {
+    use bytes::Bytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(b: &Bytes, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(b)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Bytes, D::Error> {
+        Vec::<u8>::deserialize(d).map(Bytes::from)
+    }
+}
+
+/// bincode can't deserialize self-describing values like `serde_json::Value`, so they're stored as JSON text
+pub(crate) mod json_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use serde_json::{Map, Value};
+
+    pub fn serialize<S: Serializer>(m: &Map<String, Value>, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&Value::Object(m.clone()).to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Map<String, Value>, D::Error> {
+        let text = String::deserialize(d)?;
+        serde_json::from_str(&text).map_err(D::Error::custom)
+    }
+}
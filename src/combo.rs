@@ -0,0 +1,214 @@
+use ssbm_utils::prelude::{Attack, Port};
+
+use crate::{
+    frame::FIRST_FRAME,
+    game::Game,
+    postframe::{flags, PostFrame},
+    stats::is_death_state,
+};
+
+/// Number of frames the victim can be out of hitstun without being hit before the combo is considered over
+pub const COMBO_RESET_FRAMES: u32 = 45;
+
+/// A single hit within a combo
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComboHit {
+    /// Frame on which the victim's percent went up
+    pub frame_index: i32,
+    /// Raw move ID, see [`ComboHit::attack`]
+    pub attack_id: u8,
+    pub damage: f32,
+}
+
+impl ComboHit {
+    #[inline]
+    pub fn attack(&self) -> Option<Attack> {
+        Attack::from_repr(self.attack_id)
+    }
+}
+
+/// A string of hits by one attacker on one victim, with no more than [`COMBO_RESET_FRAMES`] of the victim being
+/// actionable between any two of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Combo {
+    pub attacker: Port,
+    pub victim: Port,
+    pub start_frame: i32,
+    pub end_frame: i32,
+    /// Victim's percent before the first hit
+    pub start_percent: f32,
+    /// Victim's percent after the last hit
+    pub end_percent: f32,
+    pub hits: Vec<ComboHit>,
+    /// True if the combo ended with the victim losing a stock
+    pub did_kill: bool,
+}
+
+impl Combo {
+    /// Total percent dealt over the course of the combo
+    pub fn damage(&self) -> f32 {
+        self.hits.iter().map(|h| h.damage).sum()
+    }
+
+    /// True if the opening turned into more than a single hit, or into a kill
+    pub fn is_conversion(&self) -> bool {
+        self.hits.len() > 1 || self.did_kill
+    }
+}
+
+impl Game {
+    /// Every combo in the game, ordered by victim port and then by start frame
+    pub fn combos(&self) -> Vec<Combo> {
+        let timelines: Vec<_> = self
+            .active_ports()
+            .map(|port| (port, self.post_frames_indexed(port)))
+            .collect();
+
+        timelines
+            .iter()
+            .flat_map(|(victim, _)| self.combos_on(*victim, &timelines))
+            .collect()
+    }
+
+    /// The combo the given port performed that dealt the most total percent
+    pub fn longest_combo(&self, port: Port) -> Option<Combo> {
+        self.combos()
+            .into_iter()
+            .filter(|c| c.attacker == port)
+            .max_by(|a, b| a.damage().total_cmp(&b.damage()))
+    }
+
+    /// Fraction (0.0-1.0) of the given port's openings that they converted, see [`Combo::is_conversion`].
+    /// `None` if they never got an opening.
+    pub fn conversion_rate(&self, port: Port) -> Option<f32> {
+        let openings: Vec<Combo> = self
+            .combos()
+            .into_iter()
+            .filter(|c| c.attacker == port)
+            .collect();
+        let conversions = openings.iter().filter(|c| c.is_conversion()).count();
+
+        (!openings.is_empty()).then(|| conversions as f32 / openings.len() as f32)
+    }
+
+    fn combos_on(&self, victim: Port, timelines: &[(Port, Vec<Option<&PostFrame>>)]) -> Vec<Combo> {
+        let frames = self.post_frames(victim);
+        let mut combos = Vec::new();
+        let mut current: Option<Combo> = None;
+        let mut since_stun = 0;
+
+        for w in frames.windows(2) {
+            let (prev, curr) = (w[0], w[1]);
+            let idx = curr.frame_index;
+
+            if is_death_state(curr.action_state) {
+                if let Some(mut combo) = current.take() {
+                    combo.did_kill = !is_death_state(prev.action_state);
+                    combo.end_frame = idx;
+                    combos.push(combo);
+                }
+                continue;
+            }
+
+            let damage = curr.percent - prev.percent;
+            let attacker = curr.attacker();
+
+            if let (true, Some(attacker)) = (damage > 0.0, attacker) {
+                since_stun = 0;
+                if current.as_ref().is_some_and(|c| c.attacker != attacker) {
+                    combos.extend(current.take());
+                }
+                let combo = current.get_or_insert_with(|| Combo {
+                    attacker,
+                    victim,
+                    start_frame: idx,
+                    end_frame: idx,
+                    start_percent: prev.percent,
+                    end_percent: prev.percent,
+                    hits: Vec::new(),
+                    did_kill: false,
+                });
+                combo.hits.push(ComboHit {
+                    frame_index: idx,
+                    attack_id: attack_landed(timelines, attacker, idx),
+                    damage,
+                });
+                combo.end_frame = idx;
+                combo.end_percent = curr.percent;
+                continue;
+            }
+
+            let Some(combo) = current.as_mut() else {
+                continue;
+            };
+            if curr.has_flag(flags::HITSTUN | flags::HITLAG) {
+                since_stun = 0;
+            } else {
+                since_stun += 1;
+            }
+            combo.end_frame = idx;
+            if since_stun > COMBO_RESET_FRAMES {
+                combos.extend(current.take());
+            }
+        }
+        combos.extend(current);
+
+        combos
+    }
+}
+
+/// The attacker's `last_attack_landed` on the given frame, 0 if they have no frame there
+fn attack_landed(
+    timelines: &[(Port, Vec<Option<&PostFrame>>)],
+    attacker: Port,
+    frame_index: i32,
+) -> u8 {
+    let Some((_, timeline)) = timelines.iter().find(|(p, _)| *p == attacker) else {
+        return 0;
+    };
+    usize::try_from(frame_index as i64 - FIRST_FRAME as i64)
+        .ok()
+        .and_then(|i| timeline.get(i).copied().flatten())
+        .map_or(0, |f| f.last_attack_landed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combo(hits: usize, did_kill: bool) -> Combo {
+        let hits = (0..hits)
+            .map(|i| ComboHit {
+                frame_index: i as i32 * 10,
+                attack_id: 0,
+                damage: 10.0,
+            })
+            .collect();
+        Combo {
+            attacker: Port::P1,
+            victim: Port::P2,
+            start_frame: 0,
+            end_frame: 0,
+            start_percent: 0.0,
+            end_percent: 0.0,
+            hits,
+            did_kill,
+        }
+    }
+
+    #[test]
+    fn single_hit_isnt_a_conversion() {
+        assert!(!combo(1, false).is_conversion());
+    }
+
+    #[test]
+    fn multiple_hits_are_a_conversion() {
+        assert!(combo(2, false).is_conversion());
+        assert!(combo(5, false).is_conversion());
+    }
+
+    #[test]
+    fn single_hit_that_kills_is_a_conversion() {
+        assert!(combo(1, true).is_conversion());
+    }
+}
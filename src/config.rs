@@ -0,0 +1,86 @@
+use std::{path::PathBuf, sync::Arc};
+
+use chrono::{DateTime, FixedOffset};
+
+#[cfg(feature = "metrics")]
+use crate::metrics::MetricsSink;
+use crate::{
+    inputs::STICK_DEADZONE,
+    sink::{LogSink, ValidationSink},
+};
+
+/// Options controlling how replays are parsed and validated.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Print the declared event sizes from the EventPayloads table before parsing any events
+    pub dump_payloads: bool,
+    /// Enforce competitive expectations (e.g. exactly 2 human players, frozen Pokemon Stadium) as hard
+    /// errors rather than accepting any player configuration or ruleset
+    pub strict: bool,
+    /// How far a parsed orientation may be from -1, 0, or 1 before it's considered invalid
+    pub orientation_epsilon: f32,
+    /// Stop parsing after this many frames (counted from the first frame, -123). The header, GameStart,
+    /// and metadata are always read in full.
+    pub max_frames: Option<usize>,
+    /// Parse files regardless of their extension. Otherwise only `.slp` files are parsed.
+    pub force: bool,
+    /// In directory mode, print a count of games per character matchup instead of validating. Only the
+    /// replay headers are read.
+    pub matchups: bool,
+    /// Skip replays whose metadata `startAt` is before this time. Replays without a start time are skipped
+    /// whenever `since` or `until` is set.
+    pub since: Option<DateTime<FixedOffset>>,
+    /// Skip replays whose metadata `startAt` is at or after this time
+    pub until: Option<DateTime<FixedOffset>>,
+    /// When an unknown event is hit mid-stream (usually the sign of a misaligned or corrupt stream), skip ahead
+    /// to the next FrameStart instead of failing
+    pub repair: bool,
+    /// How far an analog stick must be tilted on either axis before it counts as input, for stats such as APM
+    /// and idle detection, and for checking the raw stick position against the processed one
+    pub stick_deadzone: f32,
+    /// Report a panic while parsing a replay as a failed file instead of crashing. Catching panics can hide bugs,
+    /// so when `None` this is only done in directory mode, where one bad file would otherwise abort the batch.
+    pub catch_panics: Option<bool>,
+    /// Stop at the first error-level diagnostic and return it as [`crate::utils::ParseError::Validation`].
+    /// Warnings never stop parsing.
+    pub fail_fast: bool,
+    /// Instead of printing each per-frame warning, print how many of each kind there were once a replay is
+    /// done, see [`crate::sink::SummarySink`]. Only applies to [`crate::parse()`].
+    pub summary_only: bool,
+    /// Check each replay against its entry in this [`crate::fingerprint::FingerprintDb`], reporting an error on
+    /// a mismatch. Only applies to [`crate::parse()`].
+    pub fingerprint_db: Option<PathBuf>,
+    /// Record each replay's fingerprint into `fingerprint_db` instead of checking it, replacing any existing
+    /// entry for the same file name
+    pub record_fingerprints: bool,
+    /// Receives a summary of every replay parsed, see [`crate::metrics`]
+    #[cfg(feature = "metrics")]
+    pub metrics: Option<Arc<dyn MetricsSink>>,
+    /// Receives every diagnostic reported while parsing and validating. Defaults to [`LogSink`].
+    pub sink: Arc<dyn ValidationSink>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            dump_payloads: false,
+            strict: false,
+            orientation_epsilon: 1e-4,
+            max_frames: None,
+            force: false,
+            matchups: false,
+            since: None,
+            until: None,
+            repair: false,
+            stick_deadzone: STICK_DEADZONE,
+            catch_panics: None,
+            fail_fast: false,
+            summary_only: false,
+            fingerprint_db: None,
+            record_fingerprints: false,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            sink: Arc::new(LogSink),
+        }
+    }
+}
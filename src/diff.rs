@@ -0,0 +1,177 @@
+//! Field-level comparison of two parsed games, for checking that a parser change didn't alter the output.
+//!
+//! Events are compared through their [`ToJson`] representation, so the field names match the JSON output.
+
+use serde_json::Value;
+
+use crate::{game::Game, json::ToJson};
+
+/// A field that differs between two games
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    /// Which event the field belongs to, e.g. `post_frames[812]`. Frame events are indexed in stream order.
+    pub event: String,
+    /// Frame the event belongs to, for frame events
+    pub frame_index: Option<i32>,
+    /// Field name as it appears in the JSON output. `len` when one game has more events of a kind than the other,
+    /// `present` when only one game has the event at all.
+    pub field: String,
+    pub a: Value,
+    pub b: Value,
+}
+
+/// Every event that differs between `a` and `b`, with the first field that differs in each. Frame events are
+/// paired up by stream position, so a single missing or extra event shows up as a difference in every event
+/// after it.
+pub fn diff(a: &Game, b: &Game) -> Vec<FieldDiff> {
+    let mut out = Vec::new();
+
+    if a.version != b.version {
+        out.push(FieldDiff {
+            event: "game".to_string(),
+            frame_index: None,
+            field: "version".to_string(),
+            a: a.version.to_string().into(),
+            b: b.version.to_string().into(),
+        });
+    }
+    diff_event(
+        &mut out,
+        "start".to_string(),
+        a.start.to_json(),
+        b.start.to_json(),
+    );
+    diff_events(&mut out, "players", &a.players, &b.players);
+    match (&a.end, &b.end) {
+        (Some(x), Some(y)) => diff_event(&mut out, "end".to_string(), x.to_json(), y.to_json()),
+        (None, None) => (),
+        (x, y) => out.push(FieldDiff {
+            event: "end".to_string(),
+            frame_index: None,
+            field: "present".to_string(),
+            a: x.is_some().into(),
+            b: y.is_some().into(),
+        }),
+    }
+    diff_event(
+        &mut out,
+        "metadata".to_string(),
+        Value::Object(a.metadata.raw.clone()),
+        Value::Object(b.metadata.raw.clone()),
+    );
+
+    diff_events(&mut out, "frame_starts", &a.frame_starts, &b.frame_starts);
+    diff_events(&mut out, "pre_frames", &a.pre_frames, &b.pre_frames);
+    diff_events(&mut out, "item_frames", &a.item_frames, &b.item_frames);
+    diff_events(&mut out, "post_frames", &a.post_frames, &b.post_frames);
+    diff_events(&mut out, "frame_ends", &a.frame_ends, &b.frame_ends);
+
+    out
+}
+
+fn diff_events<T: ToJson>(out: &mut Vec<FieldDiff>, name: &str, a: &[T], b: &[T]) {
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        diff_event(out, format!("{name}[{i}]"), x.to_json(), y.to_json());
+    }
+
+    if a.len() != b.len() {
+        out.push(FieldDiff {
+            event: name.to_string(),
+            frame_index: None,
+            field: "len".to_string(),
+            a: a.len().into(),
+            b: b.len().into(),
+        });
+    }
+}
+
+/// Records the first field that differs between two events, if any
+fn diff_event(out: &mut Vec<FieldDiff>, event: String, a: Value, b: Value) {
+    if a == b {
+        return;
+    }
+    let (Value::Object(a), Value::Object(b)) = (a, b) else {
+        return;
+    };
+
+    // keys missing from one side are compared as null, optional fields are left out of the JSON entirely
+    let Some(field) = a
+        .keys()
+        .chain(b.keys().filter(|k| !a.contains_key(*k)))
+        .find(|k| a.get(*k) != b.get(*k))
+    else {
+        return;
+    };
+
+    let frame_index = a
+        .get("frame_index")
+        .or_else(|| a.get("frame_idx"))
+        .and_then(Value::as_i64)
+        .map(|i| i as i32);
+
+    out.push(FieldDiff {
+        event,
+        frame_index,
+        field: field.clone(),
+        a: a.get(field).cloned().unwrap_or(Value::Null),
+        b: b.get(field).cloned().unwrap_or(Value::Null),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{parse::validate_bytes, Config};
+
+    fn game() -> Game {
+        let data = include_bytes!("../test_replays/non_broken.slp");
+        validate_bytes(Bytes::from_static(data), &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn identical_games_dont_differ() {
+        let a = game();
+        assert_eq!(diff(&a, &a.clone()), Vec::new());
+    }
+
+    #[test]
+    fn first_differing_field_per_event() {
+        let a = game();
+        let mut b = a.clone();
+        b.frame_starts[10].random_seed ^= 1;
+        // only the first field that differs is reported
+        b.post_frames[20].percent += 1.0;
+        b.post_frames[20].stocks += 1;
+
+        let diffs = diff(&a, &b);
+        let fields: Vec<_> = diffs
+            .iter()
+            .map(|d| (d.event.as_str(), d.field.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("frame_starts[10]", "random_seed"),
+                ("post_frames[20]", "percent")
+            ]
+        );
+        assert_eq!(diffs[0].frame_index, Some(a.frame_starts[10].frame_idx));
+        assert_eq!(diffs[1].frame_index, Some(a.post_frames[20].frame_index));
+    }
+
+    #[test]
+    fn event_count_mismatch() {
+        let a = game();
+        let mut b = a.clone();
+        b.pre_frames.pop();
+
+        let diffs = diff(&a, &b);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].event, "pre_frames");
+        assert_eq!(diffs[0].field, "len");
+        assert_eq!(diffs[0].a, a.pre_frames.len());
+        assert_eq!(diffs[0].b, b.pre_frames.len());
+    }
+}
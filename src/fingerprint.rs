@@ -0,0 +1,246 @@
+//! Fingerprints of known-good replays, for catching bit rot or tampering in long-term storage.
+//!
+//! A [`Fingerprint`] is a hash of the replay's events and metadata, plus a few derived fields (duration,
+//! characters, final stocks) that make a mismatch easier to interpret. A [`FingerprintDb`] maps file names to
+//! fingerprints and is stored as a JSON object, so it stays readable (and diffable) alongside the replays.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::{fs, path::Path};
+
+#[cfg(feature = "fs")]
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+
+use crate::game::Game;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a. Used instead of `std`'s hasher, whose output isn't guaranteed to stay the same across Rust
+/// releases, which would invalidate every stored fingerprint.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |h, b| (h ^ *b as u64).wrapping_mul(FNV_PRIME))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// Hash of every raw event (command byte and payload, in stream order) and the metadata
+    pub content_hash: u64,
+    /// Number of distinct frame indices, rollbacks excluded
+    pub frames: u32,
+    /// Character select screen choice per port, `None` for empty ports
+    pub characters: [Option<String>; 4],
+    /// Stocks on each port's last frame
+    pub final_stocks: [Option<u8>; 4],
+}
+
+impl Fingerprint {
+    /// Names of the fields that differ between the two fingerprints, empty if they match
+    pub fn mismatches(&self, other: &Fingerprint) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.content_hash != other.content_hash {
+            fields.push("content_hash");
+        }
+        if self.frames != other.frames {
+            fields.push("frames");
+        }
+        if self.characters != other.characters {
+            fields.push("characters");
+        }
+        if self.final_stocks != other.final_stocks {
+            fields.push("final_stocks");
+        }
+
+        fields
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            // as a string, JSON readers that use doubles can't hold a full u64
+            "content_hash": format!("{:016x}", self.content_hash),
+            "frames": self.frames,
+            "characters": self.characters,
+            "final_stocks": self.final_stocks,
+        })
+    }
+
+    /// `None` if any field is missing or malformed
+    pub fn from_json(val: &Value) -> Option<Self> {
+        let content_hash = u64::from_str_radix(val.get("content_hash")?.as_str()?, 16).ok()?;
+        let frames = u32::try_from(val.get("frames")?.as_u64()?).ok()?;
+
+        let characters: Vec<Option<String>> = val
+            .get("characters")?
+            .as_array()?
+            .iter()
+            .map(|c| match c {
+                Value::Null => Some(None),
+                c => c.as_str().map(|c| Some(c.to_string())),
+            })
+            .collect::<Option<_>>()?;
+        let final_stocks: Vec<Option<u8>> = val
+            .get("final_stocks")?
+            .as_array()?
+            .iter()
+            .map(|s| match s {
+                Value::Null => Some(None),
+                s => s.as_u64().and_then(|s| u8::try_from(s).ok()).map(Some),
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Self {
+            content_hash,
+            frames,
+            characters: characters.try_into().ok()?,
+            final_stocks: final_stocks.try_into().ok()?,
+        })
+    }
+}
+
+impl Game {
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut hash = FNV_OFFSET;
+        for event in &self.raw_events {
+            hash = fnv1a(hash, &[event.code]);
+            hash = fnv1a(hash, &event.payload);
+        }
+        hash = fnv1a(
+            hash,
+            Value::Object(self.metadata.raw.clone())
+                .to_string()
+                .as_bytes(),
+        );
+
+        let frames = self.frame_count() as u32;
+
+        let mut characters: [Option<String>; 4] = Default::default();
+        let mut final_stocks = [None; 4];
+        for port in self.active_ports() {
+            characters[port as usize] = Some(self.players[port as usize].character.to_string());
+            final_stocks[port as usize] = self.post_frames(port).last().map(|f| f.stocks);
+        }
+
+        Fingerprint {
+            content_hash: hash,
+            frames,
+            characters,
+            final_stocks,
+        }
+    }
+}
+
+/// Fingerprints keyed by file name (not the full path, so the collection can be moved)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FingerprintDb {
+    pub entries: BTreeMap<String, Fingerprint>,
+}
+
+impl FingerprintDb {
+    pub fn to_json(&self) -> Value {
+        Value::Object(
+            self.entries
+                .iter()
+                .map(|(name, fp)| (name.clone(), fp.to_json()))
+                .collect::<Map<_, _>>(),
+        )
+    }
+
+    /// Entries that aren't valid fingerprints are skipped
+    pub fn from_json(val: &Value) -> Self {
+        let entries = val
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, fp)| Some((name.clone(), Fingerprint::from_json(fp)?)))
+            .collect();
+
+        Self { entries }
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(Self::from_json(&serde_json::from_str(&text)?))
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(&self.to_json())?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint() -> Fingerprint {
+        Fingerprint {
+            // above 2^53, which a double can't hold exactly
+            content_hash: 0xfedc_ba98_7654_3210,
+            frames: 8123,
+            characters: [
+                Some("Fox".to_string()),
+                None,
+                Some("Marth".to_string()),
+                None,
+            ],
+            final_stocks: [Some(0), None, Some(2), None],
+        }
+    }
+
+    #[test]
+    fn fingerprint_json_round_trips() {
+        let fp = fingerprint();
+        assert_eq!(Fingerprint::from_json(&fp.to_json()), Some(fp));
+    }
+
+    #[test]
+    fn malformed_fingerprints_are_rejected() {
+        let mut val = fingerprint().to_json();
+        val["final_stocks"] = json!([0, null, 2]);
+        assert_eq!(Fingerprint::from_json(&val), None);
+
+        let mut val = fingerprint().to_json();
+        val["content_hash"] = json!(12345);
+        assert_eq!(Fingerprint::from_json(&val), None);
+
+        // invalid entries are skipped rather than failing the whole database
+        let db = FingerprintDb::from_json(&json!({
+            "good.slp": fingerprint().to_json(),
+            "bad.slp": { "frames": 10 },
+        }));
+        assert_eq!(db.entries.keys().collect::<Vec<_>>(), ["good.slp"]);
+    }
+
+    #[test]
+    fn mismatched_fields() {
+        let a = fingerprint();
+        let mut b = fingerprint();
+        assert!(a.mismatches(&b).is_empty());
+
+        b.frames += 1;
+        b.final_stocks[2] = Some(1);
+        assert_eq!(a.mismatches(&b), ["frames", "final_stocks"]);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn fingerprint_db_save_and_load() {
+        let mut db = FingerprintDb::default();
+        db.entries.insert("game.slp".to_string(), fingerprint());
+
+        let path = std::env::temp_dir().join(format!(
+            "slp_validate_fingerprints_{}.json",
+            std::process::id()
+        ));
+        db.save(&path).unwrap();
+        let loaded = FingerprintDb::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), db);
+    }
+}
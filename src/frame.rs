@@ -1,43 +1,97 @@
+use anyhow::Result;
 use bytes::{Buf, Bytes};
 
-use crate::utils::Version;
+use crate::utils::{ensure_len, Version};
+
+/// Index of the first frame of every game. Frames -123 through -1 are the countdown before "GO!"
+pub const FIRST_FRAME: i32 = -123;
+
+/// First frame players can act on (same as slippi-js). Before it, characters are frozen in their entry animation
+/// while the countdown starts.
+pub const FIRST_PLAYABLE_FRAME: i32 = -39;
+
+/// Largest backwards jump in frame index that's treated as a rollback rather than a broken frame order.
+/// Slippi itself never rolls back more than 7 frames.
+pub const MAX_ROLLBACK: i32 = 10;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct FrameStart {
     pub frame_idx: i32,
+    /// State of the game's RNG at the start of the frame, see [`crate::rng`]
+    pub random_seed: u32,
     pub frame_counter: Option<u32>,
+    /// Position of the event's command byte in the replay file, for seeking into the original data. Set by
+    /// the parser, 0 for events constructed directly.
+    pub offset: usize,
 }
 
 impl FrameStart {
-    pub fn new(mut stream: Bytes, version: Version) -> FrameStart {
+    /// Size of the event payload for the given replay version
+    pub fn expected_size(version: Version) -> usize {
+        if version.at_least(3, 10, 0) {
+            12
+        } else {
+            8
+        }
+    }
+
+    pub fn new(mut stream: Bytes, version: Version) -> Result<FrameStart> {
+        ensure_len(&stream, Self::expected_size(version), "FrameStart")?;
         let frame_idx = stream.get_i32();
-        // random seed
-        stream.get_u32();
+        let random_seed = stream.get_u32();
         let frame_counter = version.at_least(3, 10, 0).then(|| stream.get_u32());
-        FrameStart {
+        Ok(FrameStart {
             frame_idx,
+            random_seed,
             frame_counter,
-        }
+            offset: 0,
+        })
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct FrameEnd {
     pub frame_idx: i32,
     pub latest_finalized: Option<i32>,
+    /// Position of the command byte in the file, see [`FrameStart::offset`]
+    pub offset: usize,
 }
 
 impl FrameEnd {
-    pub fn new(mut stream: Bytes, version: Version) -> FrameEnd {
+    /// Size of the event payload for the given replay version
+    pub fn expected_size(version: Version) -> usize {
+        if version.at_least(3, 7, 0) {
+            8
+        } else {
+            4
+        }
+    }
+
+    pub fn new(mut stream: Bytes, version: Version) -> Result<FrameEnd> {
+        ensure_len(&stream, Self::expected_size(version), "FrameEnd")?;
         let frame_idx = stream.get_i32();
         let latest_finalized = if version.at_least(3, 7, 0) {
             Some(stream.get_i32())
         } else {
             None
         };
-        FrameEnd {
+        Ok(FrameEnd {
             frame_idx,
             latest_finalized,
-        }
+            offset: 0,
+        })
     }
 }
+
+/// A rollback observed in the frame stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rollback {
+    /// The latest frame that had been simulated when the rollback occurred
+    pub from: i32,
+    /// The frame the game rolled back to
+    pub to: i32,
+    /// The number of frames that were simulated again, i.e. `to..=from`
+    pub resimulated: u32,
+}
@@ -0,0 +1,518 @@
+use std::{io::Write, time::Duration};
+
+use anyhow::Result;
+use bytes::{Buf, Bytes};
+use ssbm_utils::prelude::{Character, Item, Port};
+
+use crate::{
+    frame::{FrameEnd, FrameStart, Rollback, FIRST_FRAME},
+    game_end::GameEnd,
+    game_start::{GameStart, MatchType, Mode, Timer},
+    inputs::ControllerState,
+    itemframe::ItemFrame,
+    metadata::{Metadata, PlayedOn},
+    parse::EventType,
+    player::{Player, PlayerType},
+    postframe::PostFrame,
+    preframe::PreFrame,
+    ubjson,
+    utils::Version,
+};
+
+/// An event exactly as it appeared in the replay, minus the command byte
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+pub struct RawEvent {
+    pub code: u8,
+    #[cfg_attr(feature = "cache", serde(with = "crate::cache::bytes"))]
+    pub payload: Bytes,
+}
+
+/// The attacker responsible for a hit, as resolved through `PostFrame::instance_hit_by`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HitSource<'a> {
+    Player(&'a PostFrame),
+    Item(&'a ItemFrame),
+}
+
+/// A single parsed event, see [`Game::events`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event<'a> {
+    FrameStart(&'a FrameStart),
+    PreFrame(&'a PreFrame),
+    PostFrame(&'a PostFrame),
+    Item(&'a ItemFrame),
+    FrameEnd(&'a FrameEnd),
+    GameEnd(&'a GameEnd),
+}
+
+impl Event<'_> {
+    /// Position of the command byte in the file. `None` for GameEnd, which is always last.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Event::FrameStart(e) => Some(e.offset),
+            Event::PreFrame(e) => Some(e.offset),
+            Event::PostFrame(e) => Some(e.offset),
+            Event::Item(e) => Some(e.offset),
+            Event::FrameEnd(e) => Some(e.offset),
+            Event::GameEnd(_) => None,
+        }
+    }
+}
+
+/// A fully parsed replay. Events are stored in stream order, so frames that were rolled back appear
+/// once per simulation.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+pub struct Game {
+    pub version: Version,
+    pub start: GameStart,
+    pub players: [Player; 4],
+    pub end: Option<GameEnd>,
+    /// Length of the raw element as declared in the header, 0 for replays that were never finalized
+    pub raw_length: u32,
+    pub metadata: Metadata,
+    pub frame_starts: Vec<FrameStart>,
+    pub frame_ends: Vec<FrameEnd>,
+    pub pre_frames: Vec<PreFrame>,
+    pub post_frames: Vec<PostFrame>,
+    pub item_frames: Vec<ItemFrame>,
+    /// Every event in the raw element (EventPayloads and GameStart included) in stream order. These share
+    /// the original file's buffer rather than copying it.
+    pub raw_events: Vec<RawEvent>,
+}
+
+impl Game {
+    /// Ports with a human, CPU, or demo player in them
+    pub fn active_ports(&self) -> impl Iterator<Item = Port> + '_ {
+        self.players
+            .iter()
+            .filter(|p| p.player_type.has_frames())
+            .map(|p| p.port)
+    }
+
+    /// Unranked, Ranked, or Direct for Slippi netplay games (v3.14.0+), `MatchType::Unknown` otherwise
+    pub fn match_type(&self) -> MatchType {
+        self.start.match_type
+    }
+
+    /// VS, Online, or Training. Replays before v3.7.0 don't record the scene, for those netplay games are
+    /// still recognized by the metadata's `playedOn`.
+    pub fn mode(&self) -> Mode {
+        match self.start.mode {
+            Mode::Unknown if self.metadata.played_on == Some(PlayedOn::Network) => Mode::Online,
+            mode => mode,
+        }
+    }
+
+    /// The player in the given port, `None` if the port is empty
+    pub fn player(&self, port: Port) -> Option<&Player> {
+        let player = &self.players[port as usize];
+        player.player_type.has_frames().then_some(player)
+    }
+
+    /// The seed the game's RNG started with. Needed by anything that replays the game's RNG, see
+    /// [`Game::rng_calls`].
+    pub fn random_seed(&self) -> u32 {
+        self.start.random_seed
+    }
+
+    /// The CPU level (1-9) if the given port is a CPU, `None` for humans and empty ports
+    pub fn is_cpu(&self, port: Port) -> Option<u8> {
+        let player = &self.players[port as usize];
+        (player.player_type == PlayerType::CPU).then_some(player.cpu_level)
+    }
+
+    /// Returns the leader's (i.e. not Nana's) pre-frames for the given port, one per frame index. When a frame
+    /// was simulated more than once due to rollback, only the final simulation is kept.
+    pub fn pre_frames(&self, port: Port) -> Vec<&PreFrame> {
+        dedup(
+            self.pre_frames
+                .iter()
+                .filter(|f| f.port == port as u8 && !f.nana),
+            |f| f.frame_index,
+        )
+    }
+
+    /// Returns the leader's (i.e. not Nana's) post-frames for the given port, one per frame index. When a frame
+    /// was simulated more than once due to rollback, only the final simulation is kept.
+    pub fn post_frames(&self, port: Port) -> Vec<&PostFrame> {
+        dedup(
+            self.post_frames
+                .iter()
+                .filter(|f| f.port == port as u8 && !f.nana),
+            |f| f.frame_index,
+        )
+    }
+
+    /// Every parsed frame event, plus the GameEnd, in the exact order they appeared in the stream, rollbacks
+    /// included. The events are borrowed from the per-type vectors and ordered by their offsets, so this
+    /// costs an allocation and a sort per call. For the events' raw bytes, see `Game::raw_events`.
+    pub fn events(&self) -> Vec<Event<'_>> {
+        let mut events: Vec<Event> = Vec::with_capacity(
+            self.frame_starts.len()
+                + self.pre_frames.len()
+                + self.post_frames.len()
+                + self.item_frames.len()
+                + self.frame_ends.len()
+                + 1,
+        );
+        events.extend(self.frame_starts.iter().map(Event::FrameStart));
+        events.extend(self.pre_frames.iter().map(Event::PreFrame));
+        events.extend(self.post_frames.iter().map(Event::PostFrame));
+        events.extend(self.item_frames.iter().map(Event::Item));
+        events.extend(self.frame_ends.iter().map(Event::FrameEnd));
+        events.sort_by_key(|e| e.offset());
+        events.extend(self.end.iter().map(Event::GameEnd));
+
+        events
+    }
+
+    /// Every rollback in the replay, in the order they occurred
+    pub fn rollbacks(&self) -> Vec<Rollback> {
+        self.frame_starts
+            .windows(2)
+            .filter(|w| w[1].frame_idx < w[0].frame_idx)
+            .map(|w| Rollback {
+                from: w[0].frame_idx,
+                to: w[1].frame_idx,
+                resimulated: w[0].frame_idx.abs_diff(w[1].frame_idx).saturating_add(1),
+            })
+            .collect()
+    }
+
+    /// False for replays that were still being written or whose game crashed: there's no GameEnd, and the
+    /// events stop short of the declared raw length (or no length was declared at all).
+    pub fn is_complete(&self) -> bool {
+        if self.end.is_some() {
+            return true;
+        }
+        let read: usize = self.raw_events.iter().map(|e| e.payload.len() + 1).sum();
+
+        self.raw_length != 0 && read >= self.raw_length as usize
+    }
+
+    /// Number of distinct frame indices reached, i.e. the game's length in frames with rollbacks excluded
+    pub fn frame_count(&self) -> usize {
+        self.frame_starts
+            .iter()
+            .map(|f| f.frame_idx)
+            .max()
+            .map_or(0, |last| {
+                (last as i64 - FIRST_FRAME as i64 + 1).max(0) as usize
+            })
+    }
+
+    /// Fraction of simulated frames that were re-simulations due to rollback, `None` if there are no frames
+    pub fn rollback_rate(&self) -> Option<f32> {
+        let simulated = self.frame_starts.len();
+        (simulated > 0)
+            .then(|| simulated.saturating_sub(self.frame_count()) as f32 / simulated as f32)
+    }
+
+    /// How much was left on the in-game clock on the last frame. The clock starts counting down from
+    /// `GameStart::timer` on frame 0 at 60 frames per second, so a game that timed out has (close to) nothing
+    /// left. Always zero if the timer was turned off or counts up.
+    pub fn time_remaining_at_end(&self) -> Duration {
+        if self.start.timer_mode != Timer::CountDown {
+            return Duration::ZERO;
+        }
+        let elapsed = self
+            .frame_starts
+            .iter()
+            .map(|f| f.frame_idx)
+            .max()
+            .map_or(0, |last| last.max(-1) + 1);
+
+        self.start
+            .timer
+            .saturating_sub(Duration::from_secs_f64(elapsed as f64 / 60.0))
+    }
+
+    /// True once some FrameEnd reports `latest_finalized >= frame_idx`, i.e. the frame can no longer be rolled
+    /// back. Replays older than v3.7.0 don't record finalization, so every frame that was reached counts as
+    /// finalized.
+    pub fn is_finalized(&self, frame_idx: i32) -> bool {
+        self.frame_ends
+            .iter()
+            .any(|f| f.latest_finalized.unwrap_or(f.frame_idx) >= frame_idx)
+    }
+
+    /// Serializes the game back into the .slp format. Events are written from their raw payloads, but the raw
+    /// length is recomputed and the metadata is re-encoded from [`Metadata::raw`], so the output is only
+    /// byte-identical to the input for finalized replays written by Slippi. In-progress replays (raw length 0)
+    /// and files whose metadata was encoded differently round-trip to an equivalent file, not the same bytes.
+    pub fn write_slp<W: Write>(&self, mut out: W) -> Result<()> {
+        let raw_length: usize = self.raw_events.iter().map(|e| e.payload.len() + 1).sum();
+
+        // `{U\x03raw[$U#l`
+        out.write_all(&[
+            0x7b, 0x55, 0x03, 0x72, 0x61, 0x77, 0x5b, 0x24, 0x55, 0x23, 0x6c,
+        ])?;
+        out.write_all(&u32::try_from(raw_length)?.to_be_bytes())?;
+
+        for event in &self.raw_events {
+            out.write_all(&[event.code])?;
+            out.write_all(&event.payload)?;
+        }
+
+        // `U\x08metadata{`
+        out.write_all(&[
+            0x55, 0x08, 0x6d, 0x65, 0x74, 0x61, 0x64, 0x61, 0x74, 0x61, 0x7b,
+        ])?;
+        ubjson::write_map(&mut out, &self.metadata.raw)?;
+        // close the top level object
+        out.write_all(&[0x7d])?;
+
+        Ok(())
+    }
+
+    /// A copy of the game containing only the frames with indices in `start..=end`, for clipping out a short
+    /// sequence. GameStart, metadata, and everything else outside the frame events are kept as-is, the GameEnd
+    /// only if the range reaches the last frame. `start` is clamped to the first frame (-123), so the pre-"GO!"
+    /// frames are only included when asked for. Rolled back frames within the range are kept.
+    pub fn slice(&self, start: i32, end: i32) -> Game {
+        let start = start.max(FIRST_FRAME);
+        let in_range = |idx: i32| (start..=end).contains(&idx);
+        let last_frame = self.frame_starts.iter().map(|f| f.frame_idx).max();
+        let keep_end = last_frame.map_or(true, |last| end >= last);
+
+        let raw_events: Vec<RawEvent> = self
+            .raw_events
+            .iter()
+            .filter(|e| match EventType::from_repr(e.code) {
+                Some(
+                    EventType::FrameStart
+                    | EventType::PreFrame
+                    | EventType::PostFrame
+                    | EventType::Item
+                    | EventType::FrameEnd,
+                ) => e.payload.len() >= 4 && in_range(e.payload.slice(..4).get_i32()),
+                Some(EventType::GameEnd) => keep_end,
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        let raw_length: usize = raw_events.iter().map(|e| e.payload.len() + 1).sum();
+
+        Game {
+            version: self.version,
+            start: self.start.clone(),
+            players: self.players.clone(),
+            end: self.end.clone().filter(|_| keep_end),
+            raw_length: raw_length as u32,
+            metadata: self.metadata.clone(),
+            frame_starts: filter_frames(&self.frame_starts, |f| in_range(f.frame_idx)),
+            frame_ends: filter_frames(&self.frame_ends, |f| in_range(f.frame_idx)),
+            pre_frames: filter_frames(&self.pre_frames, |f| in_range(f.frame_index)),
+            post_frames: filter_frames(&self.post_frames, |f| in_range(f.frame_index)),
+            item_frames: filter_frames(&self.item_frames, |f| in_range(f.frame_index)),
+            raw_events,
+        }
+    }
+
+    /// Like [`Game::pre_frames`], but indexed by `frame_index - FIRST_FRAME`. Frames missing from the replay are
+    /// `None`.
+    pub fn pre_frames_indexed(&self, port: Port) -> Vec<Option<&PreFrame>> {
+        dedup_indexed(
+            self.pre_frames
+                .iter()
+                .filter(|f| f.port == port as u8 && !f.nana),
+            |f| f.frame_index,
+        )
+    }
+
+    /// Like [`Game::post_frames`], but indexed by `frame_index - FIRST_FRAME` so timelines for different ports line up.
+    /// Frames missing from the replay are `None`.
+    pub fn post_frames_indexed(&self, port: Port) -> Vec<Option<&PostFrame>> {
+        dedup_indexed(
+            self.post_frames
+                .iter()
+                .filter(|f| f.port == port as u8 && !f.nana),
+            |f| f.frame_index,
+        )
+    }
+
+    /// The character each active port spent the most frames as, in port order. Unlike `Player::character`
+    /// (the character select screen choice), this reports Sheik for a Zelda player who transformed and
+    /// mostly played Sheik.
+    pub fn characters(&self) -> Vec<Character> {
+        self.active_ports()
+            .filter_map(|port| self.main_character(port))
+            .collect()
+    }
+
+    /// The character the given port spent the most (finalized) frames as
+    pub fn main_character(&self, port: Port) -> Option<Character> {
+        let mut counts: Vec<(u8, usize)> = Vec::new();
+        for frame in self.post_frames(port) {
+            match counts.iter_mut().find(|(c, _)| *c == frame.character) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((frame.character, 1)),
+            }
+        }
+
+        counts
+            .into_iter()
+            .max_by_key(|(_, n)| *n)
+            .and_then(|(c, _)| Character::try_from_internal(c).ok())
+    }
+
+    /// The form the given port's character is in on the given frame, e.g. Sheik for a Zelda player who has
+    /// transformed. Nana's form is `Character::Nana`. `None` if the port has no frame there.
+    pub fn form_at(&self, port: Port, frame_index: i32, nana: bool) -> Option<Character> {
+        // the last simulation of a frame is the one that stuck
+        let frame = self
+            .post_frames
+            .iter()
+            .rev()
+            .find(|f| f.port == port as u8 && f.nana == nana && f.frame_index == frame_index)?;
+
+        Character::try_from_internal(frame.character).ok()
+    }
+
+    /// Resolves `victim.instance_hit_by` (v3.16.0+) to the player or item frame that carried that instance ID,
+    /// using the most recent such frame at or before the victim's frame.
+    pub fn hit_source(&self, victim: &PostFrame) -> Option<HitSource<'_>> {
+        let id = victim.instance_hit_by.filter(|id| *id != 0)?;
+        let idx = victim.frame_index;
+
+        let player = self
+            .post_frames
+            .iter()
+            .filter(|f| f.instance_id == Some(id) && f.frame_index <= idx)
+            .last();
+        let item = self
+            .item_frames
+            .iter()
+            .filter(|f| f.instance_id == Some(id) && f.frame_index <= idx)
+            .last();
+
+        match (player, item) {
+            (Some(p), Some(i)) if i.frame_index > p.frame_index => Some(HitSource::Item(i)),
+            (Some(p), _) => Some(HitSource::Player(p)),
+            (None, Some(i)) => Some(HitSource::Item(i)),
+            (None, None) => None,
+        }
+    }
+
+    /// Every item frame (rollbacks included, in stream order) for items of the given type
+    pub fn items_of_type(&self, item: Item) -> impl Iterator<Item = &ItemFrame> + '_ {
+        let id = item as u16;
+        self.item_frames.iter().filter(move |f| f.item_id == id)
+    }
+
+    /// Per-frame controller state for the given port, in frame order
+    pub fn controller_states(&self, port: Port) -> Vec<ControllerState> {
+        self.pre_frames(port)
+            .into_iter()
+            .map(|f| ControllerState {
+                frame_index: f.frame_index,
+                buttons: f.controller_buttons,
+                joystick: f.joystick,
+                cstick: f.cstick,
+                trigger_l: f.controller_l,
+                trigger_r: f.controller_r,
+                raw_stick_x: f.raw_stick_x,
+                raw_stick_y: f.raw_stick_y,
+            })
+            .collect()
+    }
+
+    /// 0-indexed placement of each port (0 is the winner), `None` for empty ports. Uses the GameEnd
+    /// placements when present (v3.13.0+). Otherwise they're computed from each player's final frame: more
+    /// stocks places higher, ties are broken by lower percent, and players tied on both share a placement.
+    pub fn placements(&self) -> [Option<u8>; 4] {
+        let mut result = [None; 4];
+
+        if let Some(placements) = self.end.as_ref().and_then(|e| e.placements.as_ref()) {
+            for (port, placement) in placements {
+                result[*port as usize] = Some(*placement as u8);
+            }
+            return result;
+        }
+
+        let finals: Vec<(Port, u8, f32)> = self
+            .active_ports()
+            .filter_map(|port| {
+                self.post_frames(port)
+                    .last()
+                    .map(|f| (port, f.stocks, f.percent))
+            })
+            .collect();
+
+        for &(port, stocks, percent) in &finals {
+            let ahead = finals
+                .iter()
+                .filter(|(_, s, p)| *s > stocks || (*s == stocks && *p < percent))
+                .count();
+            result[port as usize] = Some(ahead as u8);
+        }
+
+        result
+    }
+}
+
+/// Collapses rolled-back frames so that each frame index appears once, keeping the last (i.e. finalized)
+/// occurrence. The output is ordered by frame index.
+pub(crate) fn dedup<'a, T>(
+    frames: impl Iterator<Item = &'a T>,
+    frame_index: impl Fn(&T) -> i32,
+) -> Vec<&'a T> {
+    dedup_indexed(frames, frame_index)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Same as `dedup`, but keeps gaps as `None` so that `result[frame_index - FIRST_FRAME]` is always that frame.
+///
+/// Frames are consecutive from the first frame, so an index past the number of frames given can only come from a
+/// corrupt `frame_index`. Those frames are skipped (the parser already reported them), otherwise a single bad
+/// index could size the result at billions of entries.
+fn dedup_indexed<'a, T>(
+    frames: impl Iterator<Item = &'a T>,
+    frame_index: impl Fn(&T) -> i32,
+) -> Vec<Option<&'a T>> {
+    let frames: Vec<&'a T> = frames.collect();
+    let mut result: Vec<Option<&'a T>> = Vec::new();
+    for frame in frames.iter().copied() {
+        // anything before the first frame is already flagged during parsing
+        let Ok(i) = usize::try_from(frame_index(frame) as i64 - FIRST_FRAME as i64) else {
+            continue;
+        };
+        if i >= frames.len() {
+            continue;
+        }
+        if i >= result.len() {
+            result.resize(i + 1, None);
+        }
+        result[i] = Some(frame);
+    }
+
+    result
+}
+
+fn filter_frames<T: Clone>(frames: &[T], keep: impl Fn(&T) -> bool) -> Vec<T> {
+    frames.iter().filter(|f| keep(f)).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse::validate_bytes, Config};
+
+    #[test]
+    fn write_slp_round_trips() {
+        let original: &'static [u8] = include_bytes!("../test_replays/non_broken.slp");
+        let game = validate_bytes(Bytes::from_static(original), &Config::default()).unwrap();
+
+        let mut written = Vec::new();
+        game.write_slp(&mut written).unwrap();
+
+        assert!(
+            written == original,
+            "written replay differs from the original"
+        );
+    }
+}
@@ -1,12 +1,17 @@
 use std::collections::HashMap;
 
-use crate::utils::Version;
+use crate::{
+    sink::warn,
+    utils::{ensure_len, ParseError, Version},
+};
+use anyhow::Result;
 use bytes::{Buf, Bytes};
 use ssbm_utils::prelude::*;
 use strum::FromRepr;
 
 #[derive(Debug, Clone, PartialEq, Eq, FromRepr, Copy)]
 #[repr(u8)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub enum EndMethod {
     Unresolved,
     Timeout,
@@ -18,12 +23,14 @@ pub enum EndMethod {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, FromRepr)]
 #[repr(i8)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub enum Placement {
     Win,
     Loss,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct GameEnd {
     pub end_method: EndMethod,
     pub lras_initiator: Option<Port>,
@@ -31,8 +38,12 @@ pub struct GameEnd {
 }
 
 impl GameEnd {
-    pub fn new(mut raw: Bytes, version: Version) -> GameEnd {
-        let end_method = EndMethod::from_repr(raw.get_u8()).unwrap();
+    pub fn new(mut raw: Bytes, version: Version) -> Result<GameEnd> {
+        ensure_len(&raw, 1, "GameEnd")?;
+        let end_method_raw = raw.get_u8();
+        let end_method = EndMethod::from_repr(end_method_raw).ok_or_else(|| {
+            ParseError::Value("valid end method".to_string(), end_method_raw.to_string())
+        })?;
         let mut lras_initiator = None;
         let mut placements: Option<HashMap<Port, Placement>> = None;
 
@@ -40,7 +51,7 @@ impl GameEnd {
             lras_initiator = Port::try_from(raw.get_i8()).ok();
         }
 
-        if raw.has_remaining() {
+        if raw.remaining() >= 4 {
             let mut map = HashMap::new();
             let temp = [raw.get_i8(), raw.get_i8(), raw.get_i8(), raw.get_i8()];
             for (i, v) in temp.iter().enumerate() {
@@ -48,18 +59,20 @@ impl GameEnd {
                     continue;
                 }
 
-                map.insert(
-                    Port::from_repr(i as u8).unwrap(),
-                    Placement::from_repr(*v).unwrap(),
-                );
+                let Some(placement) = Placement::from_repr(*v) else {
+                    warn!("Invalid placement for port {}: {v}", i + 1);
+                    continue;
+                };
+
+                map.insert(Port::from_repr(i as u8).unwrap(), placement);
             }
             placements = Some(map);
         }
 
-        GameEnd {
+        Ok(GameEnd {
             end_method,
             lras_initiator,
             placements,
-        }
+        })
     }
 }
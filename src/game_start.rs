@@ -3,26 +3,31 @@ use std::time::Duration;
 use anyhow::{anyhow, Result};
 use bytes::{Buf, Bytes};
 use encoding_rs::SHIFT_JIS;
-use log::warn;
 use ssbm_utils::enums::{character::Character, stage::StageID, Port};
 use strum::{Display, FromRepr, IntoStaticStr};
 
 use crate::{
-    player::{Player, PlayerType, TeamID, TeamShade},
-    utils::Version,
+    player::{Player, PlayerType, TeamID, TeamShade, MAX_HANDICAP, MAX_PERCENT},
+    sink::warn,
+    utils::{ensure_len, Legality, ParseError, Tournament, Version},
 };
 
+/// The game's major scene at the time the replay was recorded
 #[derive(Debug, Clone, Copy, PartialEq, FromRepr, Default)]
 #[repr(u8)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mode {
     VS = 2,
+    /// Slippi netplay, including unranked, ranked, and direct
     Online = 8,
+    Training = 28,
     #[default]
     Unknown = 0,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, FromRepr, IntoStaticStr, Default, Display)]
 #[repr(u8)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub enum MatchType {
     // ascii character values for u, r, d
     Unranked = 117,
@@ -32,8 +37,18 @@ pub enum MatchType {
     Unknown = 0,
 }
 
+/// In-game language option
+#[derive(Debug, Clone, Copy, PartialEq, FromRepr)]
+#[repr(u8)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+pub enum Language {
+    Japanese = 0,
+    English = 1,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, FromRepr, Default)]
 #[repr(u8)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub enum ControllerFix {
     Off = 0,
     #[default] // this has more or less been true since like 2018
@@ -41,12 +56,16 @@ pub enum ControllerFix {
     Dween = 2,
 }
 
+/// Timer mode, the low 2 bits of game bitfield 1
 #[derive(Debug, Clone, Copy, PartialEq, Default, FromRepr)]
+#[repr(u8)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub enum Timer {
+    /// The timer is turned off
     #[default]
-    None,
-    CountDown,
-    CountUp,
+    None = 0b00,
+    CountDown = 0b10,
+    CountUp = 0b11,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Default, FromRepr)]
@@ -59,20 +78,28 @@ pub enum VsMode {
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct UCFToggles {
     pub dashback: ControllerFix,
     pub shield_drop: ControllerFix,
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct GameStart {
     /// Random seed at the start of the match
     pub random_seed: u32,
     /// True if teams mode is active, regardless of the number of players in the match
     pub teams: bool,
-    /// Simple stage ID. For stage data (blast zones, ledge locations, etc.), cast into `Stage`
+    /// True if the "Bob-omb rain" (sudden death bombs) setting is enabled
+    pub bomb_rain: bool,
+    /// Simple stage ID. For stage data (blast zones, ledge locations, etc.), cast into `Stage`. Surface heights
+    /// for the static legal stages are in [`crate::stage`]
     pub stage: StageID,
-    /// The timer setting for the match, will usually be 8 minutes (480s)
+    /// Whether the in-game clock counts down from `timer`, counts up, or is turned off
+    pub timer_mode: Timer,
+    /// The timer setting for the match, will usually be 8 minutes (480s). Set even when the timer is turned
+    /// off, see `timer_mode`
     pub timer: Duration,
     /// Damage ratio in the settings menu, should almost always be 1.0
     pub damage_ratio: f32,
@@ -88,6 +115,15 @@ pub struct GameStart {
     ///
     /// added v3.7.0
     pub netplay: Option<bool>,
+    /// VS, Online, Training, etc. Derived from the major scene, `Mode::Unknown` for replays that
+    /// predate it
+    ///
+    /// added v3.7.0
+    pub mode: Mode,
+    /// In-game language option
+    ///
+    /// added v3.12.0
+    pub language: Option<Language>,
     /// Match id, usually very similar to the default file name
     ///
     /// added v3.14.0
@@ -108,16 +144,47 @@ pub struct GameStart {
 }
 
 impl GameStart {
+    /// Size of the event payload for the given replay version
+    pub fn expected_size(version: Version) -> usize {
+        match version {
+            v if v.at_least(3, 14, 0) => 760,
+            v if v.at_least(3, 12, 0) => 701,
+            v if v.at_least(3, 11, 0) => 700,
+            v if v.at_least(3, 9, 0) => 584,
+            v if v.at_least(3, 7, 0) => 420,
+            v if v.at_least(2, 0, 0) => 418,
+            v if v.at_least(1, 5, 0) => 417,
+            v if v.at_least(1, 3, 0) => 416,
+            v if v.at_least(1, 0, 0) => 352,
+            _ => 320,
+        }
+    }
+
     // the awkward return type here is because this will only ever be constructed internally, and because it will help
     // a LOT down the line to have the players contained in the top level Game object rather than the GameStart event.
     pub fn parse(mut raw: Bytes) -> Result<(Self, Version, [Player; 4])> {
-        let version = Version::new(raw.get_u8(), raw.get_u8(), raw.get_u8());
-        raw.advance(9); // skip past revision number, game bitfields 1-4 and bomb rain
+        ensure_len(&raw, 3, "GameStart")?;
+        let mut version = [0; 3];
+        raw.copy_to_slice(&mut version);
+        let version = Version::from_bytes(version);
+        ensure_len(&raw, Self::expected_size(version) - 3, "GameStart")?;
+        raw.advance(1); // skip past revision number
+        let timer_raw = raw.get_u8() & 0b11;
+        let timer_mode = Timer::from_repr(timer_raw).unwrap_or_else(|| {
+            warn!("Invalid timer mode: {timer_raw:#04b}");
+            Timer::default()
+        });
+        raw.advance(5); // skip past game bitfields 2-4, up to the bomb rain flag
+        let bomb_rain = raw.get_u8() != 0;
+        raw.advance(1);
 
         let is_teams = raw.get_u8() != 0;
         raw.advance(5); // skip item spawn rate and self destruct score value
 
-        let stage = StageID::from_repr(raw.get_u16()).unwrap();
+        let stage_raw = raw.get_u16();
+        let stage = StageID::from_repr(stage_raw).ok_or_else(|| {
+            ParseError::Value("valid stage ID".to_string(), stage_raw.to_string())
+        })?;
 
         // timer value is given in seconds, can only be changed by full-minute increments in-game
         let timer_length = Duration::from_secs(raw.get_u32() as u64);
@@ -142,7 +209,8 @@ impl GameStart {
             let player_type = player_type.unwrap_or_default();
 
             let starting_stocks = raw.get_u8();
-            let costume = character.get_costume(raw.get_u8());
+            let costume_idx = raw.get_u8();
+            let costume = character.get_costume(costume_idx);
             let team_shade = TeamShade::from_repr(raw.get_u8());
             if matches!(player_type, PlayerType::Human | PlayerType::CPU) && team_shade.is_none() {
                 warn!("Invalid team shade for player {i}");
@@ -161,12 +229,30 @@ impl GameStart {
             let model_scale = raw.get_f32();
             raw.advance(11);
 
+            if matches!(player_type, PlayerType::Human | PlayerType::CPU) {
+                // handicap scales offense and defense and is independent of the damage fields, but none of the
+                // three can be set out of range from the game's menus
+                if handicap > MAX_HANDICAP {
+                    warn!("Handicap for player {i} is above {MAX_HANDICAP}: {handicap}");
+                }
+                if damage_start > MAX_PERCENT || damage_spawn > MAX_PERCENT {
+                    warn!("Starting/spawn damage for player {i} is above {MAX_PERCENT}: {damage_start}/{damage_spawn}");
+                }
+                if model_scale != 1.0 {
+                    warn!("Non-standard model scale for player {i}: {model_scale}");
+                }
+                if offense_ratio != 1.0 || defense_ratio != 1.0 {
+                    warn!("Non-standard offense/defense ratio for player {i}: {offense_ratio}/{defense_ratio}");
+                }
+            }
+
             players[i] = Player {
                 port: Port::from_repr(i as u8).unwrap(),
                 player_type,
                 character,
                 starting_stocks,
                 costume,
+                costume_idx,
                 team_shade: team_shade.unwrap_or_default(),
                 handicap,
                 team_id: team_id.unwrap_or_default(),
@@ -199,11 +285,15 @@ impl GameStart {
         let mut result = GameStart {
             random_seed,
             teams: is_teams,
+            bomb_rain,
             stage,
+            timer_mode,
             timer: timer_length,
             pal: is_pal,
             frozen_stadium: is_frozen_stadium,
             netplay: is_netplay,
+            mode: Mode::Unknown,
+            language: None,
             match_id,
             match_type,
             game_number,
@@ -216,8 +306,18 @@ impl GameStart {
         }
 
         for player in players.iter_mut() {
-            let dashback = ControllerFix::from_repr(raw.get_u32() as u8).unwrap();
-            let shield_drop = ControllerFix::from_repr(raw.get_u32() as u8).unwrap();
+            let dashback_raw = raw.get_u32();
+            let shield_drop_raw = raw.get_u32();
+            let (Some(dashback), Some(shield_drop)) = (
+                ControllerFix::from_repr(dashback_raw as u8),
+                ControllerFix::from_repr(shield_drop_raw as u8),
+            ) else {
+                return Err(ParseError::Value(
+                    "valid UCF toggles".to_string(),
+                    format!("{dashback_raw}, {shield_drop_raw}"),
+                )
+                .into());
+            };
             player.ucf = Some(UCFToggles {
                 dashback,
                 shield_drop,
@@ -247,19 +347,33 @@ impl GameStart {
         }
 
         raw.advance(1); // skip minor scene
-        result.netplay = Some(raw.get_u8() == 8);
+        let major_scene = raw.get_u8();
+        result.mode = Mode::from_repr(major_scene).unwrap_or_default();
+        if result.mode == Mode::Unknown {
+            warn!("Unknown major scene: {major_scene}");
+        }
+        result.netplay = Some(result.mode == Mode::Online);
 
         if !version.at_least(3, 9, 0) {
             return Ok((result, version, players));
         }
 
+        // the language option comes after the names, but decides how they're decoded
+        const LANGUAGE_OFFSET: usize = (31 + 10) * 4 + 29 * 4;
+        if version.at_least(3, 12, 0) {
+            let lang = raw[LANGUAGE_OFFSET];
+            result.language = Language::from_repr(lang);
+            if result.language.is_none() {
+                warn!("Unknown language option: {lang}");
+            }
+        }
+
         for player in players.iter_mut() {
             let mut dn_bytes = vec![0; 31];
             raw.copy_to_slice(&mut dn_bytes);
             let end = dn_bytes.iter().position(|&x| x == 0).unwrap_or(30);
             dn_bytes.truncate(end);
-            let (display_name, _, _) = SHIFT_JIS.decode(&dn_bytes);
-            player.display_name = Some(display_name.to_string());
+            player.display_name = Some(decode_name(&dn_bytes, result.language));
         }
 
         for player in players.iter_mut() {
@@ -267,10 +381,11 @@ impl GameStart {
             raw.copy_to_slice(&mut cc_bytes);
             let end = cc_bytes.iter().position(|&x| x == 0).unwrap_or(10);
             cc_bytes.truncate(end);
-            let (connect_code, _, _) = SHIFT_JIS.decode(&cc_bytes);
-            // replace the full width hash symbol with the ascii variant so people can actually type them
-            let adjusted = connect_code.replace('＃', "#");
-            player.connect_code = Some(adjusted);
+            player.connect_code = Some(decode_name(&cc_bytes, result.language));
+        }
+
+        for player in players.iter() {
+            player.validate_names();
         }
 
         if !version.at_least(3, 11, 0) {
@@ -283,7 +398,7 @@ impl GameStart {
             return Ok((result, version, players));
         }
 
-        raw.advance(1); // skip language option
+        raw.advance(1); // language option, read above
 
         if !version.at_least(3, 14, 0) {
             return Ok((result, version, players));
@@ -294,14 +409,19 @@ impl GameStart {
         let end = match_id_bytes.iter().position(|&x| x == 0).unwrap_or(50);
         match_id_bytes.truncate(end);
         let match_id_len = match_id_bytes.len();
-        result.match_id = String::from_utf8(match_id_bytes).unwrap();
+        result.match_id = String::from_utf8_lossy(&match_id_bytes).into_owned();
 
         result.game_number = Some(raw.get_u32());
         result.tiebreak_number = Some(raw.get_u32());
 
         result.match_type = {
             if match_id_len > 5 {
-                MatchType::from_repr(result.match_id.as_bytes()[5]).unwrap_or_default()
+                result
+                    .match_id
+                    .as_bytes()
+                    .get(5)
+                    .and_then(|b| MatchType::from_repr(*b))
+                    .unwrap_or_default()
             } else {
                 MatchType::Unknown
             }
@@ -322,3 +442,41 @@ impl Default for Version {
         }
     }
 }
+
+/// Decodes an in-game name. Valid UTF-8 is taken as-is unless the game's language is Japanese, everything else
+/// is decoded as SHIFT_JIS (the game's native encoding).
+fn decode_name(bytes: &[u8], language: Option<Language>) -> String {
+    let decoded = match std::str::from_utf8(bytes) {
+        Ok(s) if language != Some(Language::Japanese) => s.to_string(),
+        _ => SHIFT_JIS.decode(bytes).0.into_owned(),
+    };
+    // replace the full width hash symbol with the ascii variant so people can actually type them
+    decoded.replace('＃', "#")
+}
+
+impl GameStart {
+    /// Checks the match settings against the modern competitive ruleset. Currently this only covers
+    /// Pokemon Stadium, which must be frozen. Replays older than v2.0.0 don't record whether it was frozen.
+    pub fn legality(&self) -> Legality {
+        if self.stage == StageID::PokemonStadium {
+            match self.frozen_stadium {
+                Some(true) => (),
+                Some(false) => return Legality::Illegal("Pokémon Stadium not frozen"),
+                None => {
+                    return Legality::Undetermined(
+                        "Cannot determine whether Pokémon Stadium is frozen",
+                    )
+                }
+            }
+        }
+
+        Legality::Legal
+    }
+}
+
+impl Tournament for GameStart {
+    /// Undetermined settings are treated as legal
+    fn is_legal(&self) -> bool {
+        !matches!(self.legality(), Legality::Illegal(_))
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::Write;
+
+use ssbm_utils::{prelude::Port, types::StickPos};
+use strum::{EnumIter, IntoEnumIterator, IntoStaticStr};
+
+use crate::{game::Game, utils::Version};
+
+/// Physical controller buttons, as they appear in `PreFrame::controller_buttons`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, IntoStaticStr)]
+#[repr(u16)]
+pub enum Button {
+    DPadLeft = 0x0001,
+    DPadRight = 0x0002,
+    DPadDown = 0x0004,
+    DPadUp = 0x0008,
+    Z = 0x0010,
+    R = 0x0020,
+    L = 0x0040,
+    A = 0x0100,
+    B = 0x0200,
+    X = 0x0400,
+    Y = 0x0800,
+    Start = 0x1000,
+}
+
+impl Button {
+    /// Single character used for this button in [`Game::to_input_log`]
+    pub fn symbol(self) -> char {
+        match self {
+            Button::DPadLeft => '<',
+            Button::DPadRight => '>',
+            Button::DPadDown => 'v',
+            Button::DPadUp => '^',
+            Button::Z => 'Z',
+            Button::R => 'R',
+            Button::L => 'L',
+            Button::A => 'A',
+            Button::B => 'B',
+            Button::X => 'X',
+            Button::Y => 'Y',
+            Button::Start => 'S',
+        }
+    }
+}
+
+/// Buttons that pass through to `PreFrame::engine_buttons` unchanged. Z, L, and R are left out, the game
+/// derives extra processed bits from them (Z also presses A, the analog triggers also count as L/R).
+pub const PASSTHROUGH_BUTTONS: u16 = Button::DPadLeft as u16
+    | Button::DPadRight as u16
+    | Button::DPadDown as u16
+    | Button::DPadUp as u16
+    | Button::A as u16
+    | Button::B as u16
+    | Button::X as u16
+    | Button::Y as u16
+    | Button::Start as u16;
+
+/// Bits of `PreFrame::engine_buttons` that Melee never sets, by the first version the mask applies to. Newest
+/// last, see [`invalid_engine_buttons`].
+///
+/// | Version | Unused bits                                                         |
+/// |---------|---------------------------------------------------------------------|
+/// | 0.1.0   | 7, 13-15, 24-30 (between L and A, above Start, below "any trigger") |
+///
+/// The processed button layout hasn't changed in any release so far. A version that starts setting (or
+/// repurposes) any of these bits gets a new entry here instead of a change to the existing one, so older
+/// replays keep being checked against the layout they were recorded with.
+pub const ENGINE_BUTTON_MASKS: [(Version, u32); 1] = [(Version::new(0, 1, 0), 0x7F00_E080)];
+
+/// The mask of `PreFrame::engine_buttons` bits that should never be set in a replay of the given version
+pub fn invalid_engine_buttons(version: Version) -> u32 {
+    ENGINE_BUTTON_MASKS
+        .iter()
+        .rev()
+        .find(|(since, _)| version >= *since)
+        .map_or(ENGINE_BUTTON_MASKS[0].1, |(_, mask)| *mask)
+}
+
+/// Melee's analog stick deadzone. Tilts at or below this on both axes don't register as stick input.
+pub const STICK_DEADZONE: f32 = 0.2875;
+
+/// True if the stick is tilted past `deadzone` on either axis
+pub fn is_tilted(stick: &StickPos, deadzone: f32) -> bool {
+    stick.x.abs() > deadzone || stick.y.abs() > deadzone
+}
+
+/// The full state of a player's controller on a single frame.
+///
+/// The processed values (`joystick`, `cstick`, `trigger_l`, `trigger_r`) are what the game sees after
+/// deadzones and clamping are applied. The raw stick values are the pre-deadzone hardware values and are
+/// only present on replays new enough to record them.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ControllerState {
+    pub frame_index: i32,
+    /// Bitfield of physical buttons, see [`Button`]
+    pub buttons: u16,
+    pub joystick: StickPos,
+    pub cstick: StickPos,
+    pub trigger_l: f32,
+    pub trigger_r: f32,
+    /// added v1.2.0
+    pub raw_stick_x: Option<i8>,
+    /// added v3.15.0
+    pub raw_stick_y: Option<i8>,
+}
+
+impl ControllerState {
+    #[inline]
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.buttons & button as u16 != 0
+    }
+
+    /// Returns every button held on this frame
+    pub fn pressed(&self) -> impl Iterator<Item = Button> + '_ {
+        Button::iter().filter(|b| self.is_pressed(*b))
+    }
+}
+
+impl Game {
+    /// The given port's inputs as plain text, one line per frame in frame order (rollbacks resolved). After a
+    /// `#` header line, each line is whitespace separated:
+    ///
+    /// ```text
+    /// <frame> <buttons> <joystick x> <joystick y> <cstick x> <cstick y> <trigger l> <trigger r>
+    /// ```
+    ///
+    /// `buttons` is always 12 characters, one per [`Button`] in declaration order: its [`Button::symbol`] when
+    /// held and `.` otherwise. Sticks and triggers are the processed values, to 4 decimal places.
+    pub fn to_input_log(&self, port: Port) -> String {
+        let mut log = String::from(
+            "# frame buttons joystick_x joystick_y cstick_x cstick_y trigger_l trigger_r\n",
+        );
+
+        for state in self.controller_states(port) {
+            let buttons: String = Button::iter()
+                .map(|b| if state.is_pressed(b) { b.symbol() } else { '.' })
+                .collect();
+            // writing to a String can't fail
+            let _ = writeln!(
+                log,
+                "{} {buttons} {:.4} {:.4} {:.4} {:.4} {:.4} {:.4}",
+                state.frame_index,
+                state.joystick.x,
+                state.joystick.y,
+                state.cstick.x,
+                state.cstick.y,
+                state.trigger_l,
+                state.trigger_r,
+            );
+        }
+
+        log
+    }
+}
@@ -1,14 +1,20 @@
-use crate::utils::Version;
+use crate::sink::warn;
+use crate::utils::{ensure_len, Version};
+use anyhow::Result;
 use bytes::{Buf, Bytes};
-use log::warn;
 use ssbm_utils::prelude::*;
 use ssbm_utils::types::*;
 use ssbm_utils::{pos, vel};
 
+/// Melee can't track more than this many items at once, so no single frame should contain more item events
+pub const MAX_ITEMS_PER_FRAME: usize = 15;
 
 #[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct ItemFrame {
     pub frame_index: i32,
+    /// Position of the command byte in the file, see [`crate::frame::FrameStart::offset`]
+    pub offset: usize,
     /// The ID corresponding to the type of item that this frame data is about.
     pub item_id: u16,
     pub state: u8,
@@ -29,9 +35,24 @@ pub struct ItemFrame {
 }
 
 impl ItemFrame {
-    pub fn new(mut stream: Bytes, version: Version) -> Self {
+    /// Size of the event payload for the given replay version
+    pub fn expected_size(version: Version) -> usize {
+        if version.at_least(3, 16, 0) {
+            44
+        } else if version.at_least(3, 6, 0) {
+            42
+        } else if version.at_least(3, 2, 0) {
+            41
+        } else {
+            37
+        }
+    }
+
+    pub fn new(mut stream: Bytes, version: Version) -> Result<Self> {
+        ensure_len(&stream, Self::expected_size(version), "ItemFrame")?;
         let result = Self {
             frame_index: stream.get_i32(),
+            offset: 0,
             item_id: stream.get_u16(),
             state: stream.get_u8(),
             orientation: stream.get_f32(),
@@ -50,7 +71,13 @@ impl ItemFrame {
 
         result.validate();
 
-        result
+        Ok(result)
+    }
+
+    /// The decoded item type, `None` for IDs that don't correspond to a known item
+    #[inline]
+    pub fn item(&self) -> Option<Item> {
+        Item::from_repr(self.item_id)
     }
 
     pub fn validate(&self) {
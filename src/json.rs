@@ -0,0 +1,347 @@
+//! Stable JSON representation of a parsed [`Game`].
+//!
+//! The layout is versioned by [`SCHEMA_VERSION`], which is bumped whenever a field is renamed, removed, or
+//! changes type. Adding fields does not bump the version.
+//!
+//! * Field names are snake_case and match the Rust struct fields.
+//! * Fields that aren't present in the replay's version (i.e. `Option` fields that are `None`) are omitted
+//!   entirely rather than serialized as `null`.
+//! * Enums are serialized as their variant name (e.g. `"Fox"`, `"PokemonStadium"`), ports as `"P1"`-`"P4"`.
+//!   This includes the `port`, `character` and `action_state` of frame events. A character ID that doesn't
+//!   map to a known character is written as `"Unknown(<id>)"`, matching how unknown action states print.
+//! * `last_hit_by` and item `owner` stay numeric, since they hold sentinel values that aren't ports.
+//! * Non-finite floats (NaN, infinity) are written as `null`, as JSON has no representation for them.
+//! * The replay's Slippi version is stored as a `"major.minor.build"` string under `version`.
+
+use serde_json::{json, Map, Value};
+use ssbm_utils::prelude::{Character, Port, State};
+
+use crate::{
+    frame::{FrameEnd, FrameStart},
+    game::Game,
+    game_end::GameEnd,
+    game_start::GameStart,
+    itemframe::ItemFrame,
+    player::Player,
+    postframe::PostFrame,
+    preframe::PreFrame,
+};
+
+pub const SCHEMA_VERSION: u32 = 2;
+
+pub trait ToJson {
+    fn to_json(&self) -> Value;
+}
+
+/// Inserts `val` under `key` only if it's present
+fn insert_opt<T: Into<Value>>(map: &mut Map<String, Value>, key: &str, val: Option<T>) {
+    if let Some(v) = val {
+        map.insert(key.to_string(), v.into());
+    }
+}
+
+/// Frame events store their port as a raw index, which is always 0-3 after parsing
+fn port_name(port: u8) -> String {
+    Port::from_repr(port).map_or_else(|| port.to_string(), |p| p.to_string())
+}
+
+fn to_array<T: ToJson>(items: &[T]) -> Value {
+    Value::Array(items.iter().map(ToJson::to_json).collect())
+}
+
+impl ToJson for Game {
+    fn to_json(&self) -> Value {
+        let mut map = Map::new();
+        map.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+        map.insert(
+            "version".to_string(),
+            format!(
+                "{}.{}.{}",
+                self.version.major, self.version.minor, self.version.build
+            )
+            .into(),
+        );
+        map.insert("start".to_string(), self.start.to_json());
+        map.insert("players".to_string(), to_array(&self.players));
+        insert_opt(&mut map, "end", self.end.as_ref().map(ToJson::to_json));
+        map.insert(
+            "metadata".to_string(),
+            Value::Object(self.metadata.raw.clone()),
+        );
+        map.insert("frame_starts".to_string(), to_array(&self.frame_starts));
+        map.insert("frame_ends".to_string(), to_array(&self.frame_ends));
+        map.insert("pre_frames".to_string(), to_array(&self.pre_frames));
+        map.insert("post_frames".to_string(), to_array(&self.post_frames));
+        map.insert("item_frames".to_string(), to_array(&self.item_frames));
+
+        Value::Object(map)
+    }
+}
+
+impl ToJson for GameStart {
+    fn to_json(&self) -> Value {
+        let mut map = json!({
+            "random_seed": self.random_seed,
+            "teams": self.teams,
+            "bomb_rain": self.bomb_rain,
+            "stage": format!("{:?}", self.stage),
+            "timer_mode": format!("{:?}", self.timer_mode),
+            "timer": self.timer.as_secs(),
+            "damage_ratio": self.damage_ratio,
+            "mode": format!("{:?}", self.mode),
+            "match_id": self.match_id,
+            "match_type": self.match_type.to_string(),
+        });
+        let m = map.as_object_mut().unwrap();
+        insert_opt(m, "pal", self.pal);
+        insert_opt(m, "frozen_stadium", self.frozen_stadium);
+        insert_opt(m, "netplay", self.netplay);
+        insert_opt(m, "language", self.language.map(|l| format!("{l:?}")));
+        insert_opt(m, "game_number", self.game_number);
+        insert_opt(m, "tiebreak_number", self.tiebreak_number);
+
+        map
+    }
+}
+
+impl ToJson for Player {
+    fn to_json(&self) -> Value {
+        let mut map = json!({
+            "port": self.port.to_string(),
+            "player_type": format!("{:?}", self.player_type),
+            "character": self.character.to_string(),
+            "starting_stocks": self.starting_stocks,
+            "costume_idx": self.costume_idx,
+            "team_shade": format!("{:?}", self.team_shade),
+            "handicap": self.handicap,
+            "team_id": format!("{:?}", self.team_id),
+            "bitfield": self.bitfield,
+            "cpu_level": self.cpu_level,
+            "damage_start": self.damage_start,
+            "damage_spawn": self.damage_spawn,
+            "offense_ratio": self.offense_ratio,
+            "defense_ratio": self.defense_ratio,
+            "model_scale": self.model_scale,
+        });
+        let m = map.as_object_mut().unwrap();
+        insert_opt(m, "costume_name", self.costume_name());
+        insert_opt(
+            m,
+            "ucf",
+            self.ucf.map(|u| {
+                json!({
+                    "dashback": format!("{:?}", u.dashback),
+                    "shield_drop": format!("{:?}", u.shield_drop),
+                })
+            }),
+        );
+        insert_opt(m, "connect_code", self.connect_code.clone());
+        insert_opt(m, "display_name", self.display_name.clone());
+
+        map
+    }
+}
+
+impl ToJson for GameEnd {
+    fn to_json(&self) -> Value {
+        let mut map = json!({
+            "end_method": format!("{:?}", self.end_method),
+        });
+        let m = map.as_object_mut().unwrap();
+        insert_opt(
+            m,
+            "lras_initiator",
+            self.lras_initiator.map(|p| p.to_string()),
+        );
+        insert_opt(
+            m,
+            "placements",
+            self.placements.as_ref().map(|placements| {
+                placements
+                    .iter()
+                    .map(|(port, placement)| (port.to_string(), format!("{placement:?}").into()))
+                    .collect::<Map<String, Value>>()
+            }),
+        );
+
+        map
+    }
+}
+
+impl ToJson for FrameStart {
+    fn to_json(&self) -> Value {
+        let mut map = json!({
+            "frame_idx": self.frame_idx,
+            "random_seed": self.random_seed,
+            "offset": self.offset,
+        });
+        insert_opt(
+            map.as_object_mut().unwrap(),
+            "frame_counter",
+            self.frame_counter,
+        );
+
+        map
+    }
+}
+
+impl ToJson for FrameEnd {
+    fn to_json(&self) -> Value {
+        let mut map = json!({
+            "frame_idx": self.frame_idx,
+            "offset": self.offset,
+        });
+        insert_opt(
+            map.as_object_mut().unwrap(),
+            "latest_finalized",
+            self.latest_finalized,
+        );
+
+        map
+    }
+}
+
+impl ToJson for PreFrame {
+    fn to_json(&self) -> Value {
+        let mut map = json!({
+            "frame_index": self.frame_index,
+            "offset": self.offset,
+            "port": port_name(self.port),
+            "nana": self.nana,
+            "random_seed": self.random_seed,
+            "action_state": self.action_state.to_string(),
+            "position": { "x": self.position.x, "y": self.position.y },
+            "orientation": self.orientation,
+            "joystick": { "x": self.joystick.x, "y": self.joystick.y },
+            "cstick": { "x": self.cstick.x, "y": self.cstick.y },
+            "engine_trigger": self.engine_trigger,
+            "engine_buttons": self.engine_buttons,
+            "controller_buttons": self.controller_buttons,
+            "controller_l": self.controller_l,
+            "controller_r": self.controller_r,
+        });
+        let m = map.as_object_mut().unwrap();
+        insert_opt(m, "raw_stick_x", self.raw_stick_x);
+        insert_opt(m, "percent", self.percent);
+        insert_opt(m, "raw_stick_y", self.raw_stick_y);
+
+        map
+    }
+}
+
+impl ToJson for PostFrame {
+    fn to_json(&self) -> Value {
+        let character = Character::try_from_internal(self.character).ok();
+        let mut map = json!({
+            "frame_index": self.frame_index,
+            "offset": self.offset,
+            "port": port_name(self.port),
+            "nana": self.nana,
+            "character": character.map_or_else(|| format!("Unknown({})", self.character), |c| c.to_string()),
+            "action_state": State::from_state_and_char(self.action_state, character).to_string(),
+            "position": { "x": self.position.x, "y": self.position.y },
+            "orientation": self.orientation,
+            "percent": self.percent,
+            "shield_health": self.shield_health,
+            "last_attack_landed": self.last_attack_landed,
+            "combo_count": self.combo_count,
+            "last_hit_by": self.last_hit_by,
+            "stocks": self.stocks,
+        });
+        let m = map.as_object_mut().unwrap();
+        insert_opt(m, "state_frame", self.state_frame);
+        insert_opt(m, "flags", self.flags);
+        insert_opt(m, "misc_as", self.misc_as);
+        insert_opt(m, "is_grounded", self.is_grounded);
+        insert_opt(m, "last_ground_id", self.last_ground_id);
+        insert_opt(m, "jumps_remaining", self.jumps_remaining);
+        insert_opt(m, "l_cancel", self.l_cancel);
+        insert_opt(m, "hurtbox_state", self.hurtbox_state);
+        insert_opt(
+            m,
+            "air_velocity",
+            self.air_velocity
+                .as_ref()
+                .map(|v| json!({ "x": v.x, "y": v.y })),
+        );
+        insert_opt(
+            m,
+            "knockback",
+            self.knockback
+                .as_ref()
+                .map(|v| json!({ "x": v.x, "y": v.y })),
+        );
+        insert_opt(
+            m,
+            "ground_velocity",
+            self.ground_velocity
+                .as_ref()
+                .map(|v| json!({ "x": v.x, "y": v.y })),
+        );
+        insert_opt(m, "hitlag_remaining", self.hitlag_remaining);
+        insert_opt(m, "animation_index", self.animation_index);
+        insert_opt(m, "instance_hit_by", self.instance_hit_by);
+        insert_opt(m, "instance_id", self.instance_id);
+
+        map
+    }
+}
+
+impl ToJson for ItemFrame {
+    fn to_json(&self) -> Value {
+        let mut map = json!({
+            "frame_index": self.frame_index,
+            "offset": self.offset,
+            "item_id": self.item_id,
+            "state": self.state,
+            "orientation": self.orientation,
+            "velocity": { "x": self.velocity.x, "y": self.velocity.y },
+            "position": { "x": self.position.x, "y": self.position.y },
+            "damage_taken": self.damage_taken,
+            "expiration_timer": self.expiration_timer,
+            "spawn_id": self.spawn_id,
+        });
+        let m = map.as_object_mut().unwrap();
+        insert_opt(m, "missile_type", self.missile_type);
+        insert_opt(m, "turnip_type", self.turnip_type);
+        insert_opt(m, "launched", self.launched);
+        insert_opt(m, "charge_power", self.charge_power);
+        insert_opt(m, "owner", self.owner);
+        insert_opt(m, "instance_id", self.instance_id);
+
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{parse::validate_bytes, Config};
+
+    fn game() -> Game {
+        let data = include_bytes!("../test_replays/non_broken.slp");
+        validate_bytes(Bytes::from_static(data), &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn frames_use_names_like_players() {
+        let game = game();
+        let player = game.players[0].to_json();
+        let pre = game.pre_frames[0].to_json();
+        let post = game.post_frames[0].to_json();
+
+        assert_eq!(pre["port"], player["port"]);
+        assert_eq!(post["port"], player["port"]);
+        assert!(post["character"].is_string());
+        assert!(post["action_state"].is_string());
+    }
+
+    #[test]
+    fn nan_is_null() {
+        let mut frame = game().post_frames[0].clone();
+        frame.percent = f32::NAN;
+        assert_eq!(frame.to_json()["percent"], Value::Null);
+    }
+}
@@ -0,0 +1,138 @@
+use ssbm_utils::prelude::Port;
+
+use crate::{
+    game::Game,
+    postframe::{hurtbox, PostFrame},
+    stage::ledge_x,
+    stats::is_death_state,
+};
+
+/// Number of frames after letting go of the ledge within which a regrab or ledgedash still counts as part of
+/// the same ledge option
+pub const LEDGE_OPTION_WINDOW: usize = 60;
+
+/// `CliffCatch`, the first frame of grabbing the ledge
+const CLIFF_CATCH: u16 = 252;
+/// `EscapeAir`, airdodge
+const ESCAPE_AIR: u16 = 236;
+/// `LandingFallSpecial`, the landing lag after an airdodge or special fall
+const LANDING_FALL_SPECIAL: u16 = 43;
+
+/// Ledge hang, getups, getup attacks, rolls, and jumps (`CliffCatch` through `CliffJumpQuick2`)
+pub fn is_ledge_state(action_state: u16) -> bool {
+    (252..=263).contains(&action_state)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgeOptionKind {
+    /// Normal getup onto the stage
+    Getup,
+    GetupAttack,
+    Roll,
+    Jump,
+    /// Let go of the ledge and grabbed it again
+    Regrab,
+    /// Let go of the ledge, airdodged, and landed on stage.
+    Ledgedash {
+        /// Frames of ledge intangibility left once the player could act after landing. Requires
+        /// `PostFrame::hurtbox_state` (v3.1.0+).
+        galint: Option<u32>,
+    },
+    /// Let go of the ledge (or was knocked off of it) without one of the above following
+    Drop,
+}
+
+/// A single ledge grab and what the player did with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedgeOption {
+    /// First frame of `CliffCatch`
+    pub grab_frame: i32,
+    /// First frame the player was no longer in a ledge state
+    pub leave_frame: i32,
+    pub kind: LedgeOptionKind,
+}
+
+impl Game {
+    /// Every ledge grab by the given port, classified by the option taken out of it. Grabs the game ended
+    /// during are left out.
+    pub fn ledge_options(&self, port: Port) -> Vec<LedgeOption> {
+        let frames = self.post_frames(port);
+        let ledge = ledge_x(self.start.stage);
+        let mut options = Vec::new();
+
+        let mut i = 1;
+        while i < frames.len() {
+            if frames[i].action_state != CLIFF_CATCH || is_ledge_state(frames[i - 1].action_state) {
+                i += 1;
+                continue;
+            }
+
+            let grab_frame = frames[i].frame_index;
+            let mut last = i;
+            while last + 1 < frames.len() && is_ledge_state(frames[last + 1].action_state) {
+                last += 1;
+            }
+            let Some(leave) = frames.get(last + 1) else {
+                break;
+            };
+
+            let kind = match frames[last].action_state {
+                254 | 255 => LedgeOptionKind::Getup,
+                256 | 257 => LedgeOptionKind::GetupAttack,
+                258 | 259 => LedgeOptionKind::Roll,
+                260..=263 => LedgeOptionKind::Jump,
+                _ => after_drop(&frames[last + 1..], ledge),
+            };
+            options.push(LedgeOption {
+                grab_frame,
+                leave_frame: leave.frame_index,
+                kind,
+            });
+
+            i = last + 1;
+        }
+
+        options
+    }
+}
+
+/// Classifies what happened after the player let go of the ledge. `frames` starts on the first frame off of
+/// the ledge.
+fn after_drop(frames: &[&PostFrame], ledge: Option<f32>) -> LedgeOptionKind {
+    let mut airdodged = false;
+
+    for (i, frame) in frames.iter().take(LEDGE_OPTION_WINDOW).enumerate() {
+        match frame.action_state {
+            CLIFF_CATCH => return LedgeOptionKind::Regrab,
+            ESCAPE_AIR => airdodged = true,
+            LANDING_FALL_SPECIAL if airdodged => {
+                if ledge.is_some_and(|x| frame.position.x.abs() > x) {
+                    return LedgeOptionKind::Drop;
+                }
+                return LedgeOptionKind::Ledgedash {
+                    galint: galint(&frames[i..]),
+                };
+            }
+            s if is_death_state(s) => return LedgeOptionKind::Drop,
+            _ => (),
+        }
+    }
+
+    LedgeOptionKind::Drop
+}
+
+/// Number of intangible frames starting from the first actionable frame after the airdodge landing. `frames`
+/// starts on the first frame of landing lag.
+fn galint(frames: &[&PostFrame]) -> Option<u32> {
+    let actionable = frames
+        .iter()
+        .position(|f| f.action_state != LANDING_FALL_SPECIAL)?;
+    frames[actionable].hurtbox_state?;
+
+    Some(
+        frames[actionable..]
+            .iter()
+            .take_while(|f| f.hurtbox_state == Some(hurtbox::INTANGIBLE))
+            .count() as u32,
+    )
+}
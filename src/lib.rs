@@ -1,13 +1,45 @@
+pub mod advantage;
+pub mod anticheat;
+#[cfg(feature = "archive")]
+pub mod archive;
+#[cfg(feature = "cache")]
+pub mod cache;
+pub mod combo;
+pub mod config;
+pub mod diff;
+pub mod fingerprint;
 pub mod frame;
+pub mod game;
 pub mod game_end;
 pub mod game_start;
+pub mod inputs;
 pub mod itemframe;
+pub mod json;
+pub mod ledge;
+pub mod match_set;
+pub mod metadata;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod parse;
 pub mod player;
 pub mod postframe;
 pub mod preframe;
+pub mod rng;
+pub mod sdi;
+pub mod sink;
+pub mod stage;
+pub mod stats;
 pub mod ubjson;
 pub mod utils;
+mod validate;
 
 
-pub use parse::parse;
\ No newline at end of file
+pub use config::Config;
+pub use diff::{diff, FieldDiff};
+pub use game::Game;
+pub use json::ToJson;
+pub use match_set::{group_by_match, MatchSet};
+#[cfg(feature = "fs")]
+pub use parse::{parse, parse_header};
+pub use parse::{validate_bytes, Parser};
+pub use sink::{LogSink, ValidationSink};
\ No newline at end of file
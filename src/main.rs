@@ -1,15 +1,79 @@
+use chrono::{DateTime, FixedOffset, NaiveDate};
 use clap::Parser;
 use slp_validate::*;
 
 #[derive(Parser, Debug)]
-#[command(version, about = "Run with a path to a .slp file or directory containing .slp files to check for any errors")]
+#[command(version, about = "Run with a path to a .slp file or directory containing .slp files to check for any errors. Use - to read a single replay from stdin")]
 struct Args {
     path: String,
+    /// Print the declared size of each event type before parsing
+    #[arg(long)]
+    dump_payloads: bool,
+    /// Reject replays that don't have exactly 2 human players, or whose settings aren't tournament legal
+    #[arg(long)]
+    strict: bool,
+    /// Stop after parsing this many frames
+    #[arg(long, value_name = "N")]
+    max_frames: Option<usize>,
+    /// Parse files even if they don't have a .slp extension
+    #[arg(long)]
+    force: bool,
+    /// Print the number of games per character matchup in a directory instead of validating
+    #[arg(long)]
+    matchups: bool,
+    /// Only parse replays started at or after this date (YYYY-MM-DD, UTC) or RFC 3339 timestamp
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    since: Option<DateTime<FixedOffset>>,
+    /// Only parse replays started before this date (YYYY-MM-DD, UTC) or RFC 3339 timestamp
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    until: Option<DateTime<FixedOffset>>,
+    /// Skip past corrupt sections of the event stream instead of stopping at the first one
+    #[arg(long)]
+    repair: bool,
+    /// Report a panic while parsing a replay as an error instead of crashing [default: true for directories, false for single files]
+    #[arg(long, value_name = "BOOL")]
+    catch_panics: Option<bool>,
+    /// Stop validating a replay at its first error
+    #[arg(long)]
+    fail_fast: bool,
+    /// Print a count of each kind of per-frame warning after each replay instead of every individual warning
+    #[arg(long)]
+    summary_only: bool,
+    /// Check each replay against its fingerprint in this file
+    #[arg(long, value_name = "FILE")]
+    fingerprints: Option<std::path::PathBuf>,
+    /// Record each replay's fingerprint into the --fingerprints file instead of checking it
+    #[arg(long, requires = "fingerprints")]
+    record_fingerprints: bool,
+}
+
+fn parse_date(s: &str) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc().fixed_offset());
+    }
+    DateTime::parse_from_rfc3339(s).map_err(|e| format!("expected YYYY-MM-DD or an RFC 3339 timestamp: {e}"))
 }
 
 fn main() {
     env_logger::builder().filter_level(log::LevelFilter::Info).format_timestamp(None).init();
     let args = Args::parse();
 
-    parse(&args.path);
+    let config = Config {
+        dump_payloads: args.dump_payloads,
+        strict: args.strict,
+        max_frames: args.max_frames,
+        force: args.force,
+        matchups: args.matchups,
+        since: args.since,
+        until: args.until,
+        repair: args.repair,
+        catch_panics: args.catch_panics,
+        fail_fast: args.fail_fast,
+        summary_only: args.summary_only,
+        fingerprint_db: args.fingerprints,
+        record_fingerprints: args.record_fingerprints,
+        ..Default::default()
+    };
+
+    parse(&args.path, &config);
 }
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use ssbm_utils::prelude::Port;
+
+use crate::game::Game;
+
+/// The games of a best-of-N set, as identified by a shared `GameStart::match_id` (v3.14.0+)
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchSet {
+    pub match_id: String,
+    /// Ordered by game number, then tiebreak number
+    pub games: Vec<Game>,
+    /// Winning port of each game in `games`, `None` if the game had no single winner
+    pub winners: Vec<Option<Port>>,
+    /// The port that won the most games, `None` if tied
+    pub winner: Option<Port>,
+}
+
+impl MatchSet {
+    fn new(match_id: String, mut games: Vec<Game>) -> Self {
+        games.sort_by_key(|g| (g.start.game_number, g.start.tiebreak_number));
+
+        let winners: Vec<Option<Port>> = games.iter().map(game_winner).collect();
+
+        let mut wins: HashMap<Port, usize> = HashMap::new();
+        for port in winners.iter().flatten() {
+            *wins.entry(*port).or_default() += 1;
+        }
+        let most = wins.values().copied().max().unwrap_or(0);
+        let mut leaders = wins.iter().filter(|(_, n)| **n == most).map(|(p, _)| *p);
+        let winner = match (leaders.next(), leaders.next()) {
+            (Some(port), None) => Some(port),
+            _ => None,
+        };
+
+        Self {
+            match_id,
+            games,
+            winners,
+            winner,
+        }
+    }
+}
+
+/// The only port in 1st place, if there is exactly one
+fn game_winner(game: &Game) -> Option<Port> {
+    let placements = game.placements();
+    let mut first = placements
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| **p == Some(0))
+        .map(|(i, _)| i as u8);
+
+    match (first.next(), first.next()) {
+        (Some(i), None) => Port::from_repr(i),
+        _ => None,
+    }
+}
+
+/// Groups games into sets by `match_id`, in the order each set was first seen. Replays without a match ID
+/// (older than v3.14.0) each become their own single-game set.
+pub fn group_by_match(games: Vec<Game>) -> Vec<MatchSet> {
+    let mut groups: Vec<(String, Vec<Game>)> = Vec::new();
+
+    for game in games {
+        let id = game.start.match_id.clone();
+        match groups.iter_mut().find(|(g, _)| !id.is_empty() && *g == id) {
+            Some((_, set)) => set.push(game),
+            None => groups.push((id, vec![game])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(id, games)| MatchSet::new(id, games))
+        .collect()
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use serde_json::{Map, Value};
+
+use crate::sink::warn;
+
+/// Where the replay was recorded, from the metadata's `playedOn`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlayedOn {
+    /// Offline Dolphin, including playback of other replays
+    Dolphin,
+    /// Slippi netplay
+    Network,
+    /// Console, via Nintendont and a Slippi-enabled Wii
+    Nintendont,
+    Other(String),
+}
+
+impl From<&str> for PlayedOn {
+    fn from(s: &str) -> Self {
+        match s {
+            "dolphin" => PlayedOn::Dolphin,
+            "network" => PlayedOn::Network,
+            "nintendont" => PlayedOn::Nintendont,
+            other => PlayedOn::Other(other.to_string()),
+        }
+    }
+}
+
+/// Per-player entry of the metadata's `players` object
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerMetadata {
+    /// Netplay display name, as reported by Dolphin
+    pub netplay_name: Option<String>,
+    /// Netplay connect code, e.g. `ABCD#123`
+    pub connect_code: Option<String>,
+    /// Number of frames played as each character, keyed by the character's internal ID
+    pub characters: HashMap<u8, u32>,
+}
+
+/// The UBJSON metadata block that follows the raw event stream
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    /// Index of the last frame written. Missing (or -1) in replays that were never finished.
+    pub last_frame: Option<i64>,
+    /// Time the game started. Older replays omit the UTC offset, in which case UTC is assumed.
+    pub start_at: Option<DateTime<FixedOffset>>,
+    pub played_on: Option<PlayedOn>,
+    /// Console nickname, only present for console (Nintendont) replays
+    pub console_nick: Option<String>,
+    /// Indexed by port
+    pub players: [Option<PlayerMetadata>; 4],
+    /// The metadata exactly as it was read, including any keys not covered above. Used when re-serializing
+    /// the replay.
+    #[cfg_attr(feature = "cache", serde(with = "crate::cache::json_string"))]
+    pub raw: Map<String, Value>,
+}
+
+impl Metadata {
+    pub fn from_map(raw: Map<String, Value>) -> Self {
+        let last_frame = raw.get("lastFrame").and_then(Value::as_i64);
+        let start_at = raw
+            .get("startAt")
+            .and_then(Value::as_str)
+            .and_then(parse_start_at);
+        let played_on = raw
+            .get("playedOn")
+            .and_then(Value::as_str)
+            .map(PlayedOn::from);
+        let console_nick = raw
+            .get("consoleNick")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        let mut players: [Option<PlayerMetadata>; 4] = Default::default();
+        if let Some(Value::Object(entries)) = raw.get("players") {
+            for (key, val) in entries {
+                let Some(slot) = key.parse::<usize>().ok().and_then(|i| players.get_mut(i)) else {
+                    warn!("Unexpected key in metadata players object: {key:?}");
+                    continue;
+                };
+                *slot = Some(PlayerMetadata::from_value(val));
+            }
+        }
+
+        Self {
+            last_frame,
+            start_at,
+            played_on,
+            console_nick,
+            players,
+            raw,
+        }
+    }
+}
+
+impl PlayerMetadata {
+    fn from_value(val: &Value) -> Self {
+        let names = val.get("names");
+        let netplay_name = names
+            .and_then(|n| n.get("netplay"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let connect_code = names
+            .and_then(|n| n.get("code"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        let mut characters = HashMap::new();
+        if let Some(Value::Object(chars)) = val.get("characters") {
+            for (id, frames) in chars {
+                match (id.parse::<u8>(), frames.as_u64()) {
+                    (Ok(id), Some(frames)) => {
+                        characters.insert(id, frames.min(u32::MAX as u64) as u32);
+                    }
+                    _ => warn!("Invalid character entry in metadata: {id:?}: {frames}"),
+                }
+            }
+        }
+
+        Self {
+            netplay_name,
+            connect_code,
+            characters,
+        }
+    }
+}
+
+fn parse_start_at(s: &str) -> Option<DateTime<FixedOffset>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt);
+    }
+    // early replays wrote local time without an offset
+    if let Ok(dt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Some(dt.and_utc().fixed_offset());
+    }
+
+    warn!("Unable to parse metadata startAt: {s:?}");
+    None
+}
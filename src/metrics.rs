@@ -0,0 +1,98 @@
+//! Numeric per-file metrics, for monitoring a service that ingests replays continuously.
+//!
+//! Unlike the [`crate::sink::ValidationSink`], which receives individual diagnostics as they happen, a
+//! [`MetricsSink`] is called once per replay with a summary of how parsing went. [`StatsdSink`] sends them to
+//! a StatsD daemon (which can in turn feed Prometheus via `statsd_exporter`).
+
+use std::{
+    fmt, io,
+    net::{ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+use crate::game::Game;
+
+/// How a single replay's parse went
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FileMetrics {
+    /// Wall time spent parsing and validating
+    pub parse_duration: Duration,
+    /// False if the replay couldn't be parsed at all
+    pub succeeded: bool,
+    /// Distinct frame indices, 0 if the replay couldn't be parsed
+    pub frames: usize,
+    /// Fraction of simulated frames that were rollbacks, `None` if the replay couldn't be parsed or has no frames
+    pub rollback_rate: Option<f32>,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+impl FileMetrics {
+    pub(crate) fn new(
+        game: Option<&Game>,
+        parse_duration: Duration,
+        errors: usize,
+        warnings: usize,
+    ) -> Self {
+        Self {
+            parse_duration,
+            succeeded: game.is_some(),
+            frames: game.map_or(0, Game::frame_count),
+            rollback_rate: game.and_then(Game::rollback_rate),
+            errors,
+            warnings,
+        }
+    }
+}
+
+pub trait MetricsSink: Send + Sync {
+    /// Called once per replay passed to [`crate::Parser::validate_bytes`], whether or not it parsed
+    fn record(&self, metrics: &FileMetrics);
+}
+
+impl fmt::Debug for dyn MetricsSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("dyn MetricsSink")
+    }
+}
+
+/// Sends each replay's metrics to a StatsD daemon over UDP, as `<prefix>.<metric>`. Send failures are
+/// ignored, metrics are best-effort and shouldn't interrupt parsing.
+#[derive(Debug)]
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+}
+
+impl StatsdSink {
+    pub fn new(addr: impl ToSocketAddrs, prefix: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+
+        Ok(Self {
+            socket,
+            prefix: prefix.to_string(),
+        })
+    }
+}
+
+impl MetricsSink for StatsdSink {
+    fn record(&self, metrics: &FileMetrics) {
+        let p = &self.prefix;
+        let mut lines = vec![
+            format!("{p}.files:1|c"),
+            format!("{p}.parse_time:{}|ms", metrics.parse_duration.as_millis()),
+            format!("{p}.frames:{}|c", metrics.frames),
+            format!("{p}.errors:{}|c", metrics.errors),
+            format!("{p}.warnings:{}|c", metrics.warnings),
+        ];
+        if !metrics.succeeded {
+            lines.push(format!("{p}.failed:1|c"));
+        }
+        if let Some(rate) = metrics.rollback_rate {
+            lines.push(format!("{p}.rollback_rate:{rate}|g"));
+        }
+
+        let _ = self.socket.send(lines.join("\n").as_bytes());
+    }
+}
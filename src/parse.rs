@@ -1,48 +1,128 @@
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+use std::{cmp::Ordering, collections::HashMap, time::Duration};
+#[cfg(feature = "fs")]
 use std::{
-    collections::HashMap,
     fs::{self, File},
-    io::Read,
+    io::{Read, Seek, SeekFrom},
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
-    time::Duration,
+    sync::Arc,
 };
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{ensure, Result};
 use bytes::{Buf, Bytes};
-use log::{debug, error, info, log_enabled, trace, warn, Level};
-use serde_json::{Map, Value};
+use log::{debug, log_enabled, trace, Level};
 use ssbm_utils::prelude::{Character, Port};
 use strum::FromRepr;
 
+#[cfg(feature = "metrics")]
+use crate::metrics::FileMetrics;
+#[cfg(any(feature = "fs", feature = "metrics"))]
+use crate::sink::counts;
 use crate::{
-    frame::{FrameEnd, FrameStart},
+    config::Config,
+    frame::{FrameEnd, FrameStart, FIRST_FRAME, MAX_ROLLBACK},
+    game::{Game, RawEvent},
     game_end::GameEnd,
-    game_start::GameStart,
-    itemframe::ItemFrame,
-    player::PlayerType,
+    game_start::{GameStart, MatchType, Mode},
+    itemframe::{ItemFrame, MAX_ITEMS_PER_FRAME},
+    metadata::Metadata,
+    player::{Player, PlayerType},
     postframe::PostFrame,
     preframe::PreFrame,
+    sink::{error, first_error, has_errors, info, warn, SinkGuard},
+    stats::is_death_state,
     ubjson,
-    utils::ParseError,
+    utils::{Legality, ParseError, Version, SPEC_VERSIONS},
 };
+#[cfg(feature = "fs")]
+use crate::{fingerprint::FingerprintDb, sink::SummarySink};
+
+#[cfg(feature = "fs")]
+pub fn parse(path: &str, config: &Config) {
+    let frame_summary = config
+        .summary_only
+        .then(|| Arc::new(SummarySink::new(config.sink.clone())));
+    let summarized;
+    let config = match &frame_summary {
+        Some(sink) => {
+            summarized = Config {
+                sink: sink.clone(),
+                ..config.clone()
+            };
+            &summarized
+        }
+        None => config,
+    };
+    let report = || frame_summary.iter().for_each(|sink| sink.report());
+    let _sink = SinkGuard::set(config.sink.clone());
+
+    let mut fingerprints = match &config.fingerprint_db {
+        Some(db) if config.record_fingerprints && !db.exists() => Some(FingerprintDb::default()),
+        Some(db) => match FingerprintDb::load(db) {
+            Ok(db) => Some(db),
+            Err(e) => {
+                error!("Unable to read fingerprints from {}: {e}", db.display());
+                return;
+            }
+        },
+        None => None,
+    };
+
+    if path == "-" {
+        info!("Parsing replay from stdin");
+
+        let result = get_stdin_contents().and_then(|data| validate_bytes(data, config));
+        report();
+        if let Err(e) = result {
+            error!("Error during parsing: {e}");
+        }
+        return;
+    }
 
-pub fn parse(path: &str) {
     let f_path = Path::new(path);
     if f_path.is_file() {
         info!("Parsing file {path}");
 
-        if let Err(e) = validate_game(f_path.to_path_buf()) {
-            error!("Error during parsing: {e}");
+        if !config.force && !has_slp_extension(f_path) {
+            let ext = f_path.extension().map_or("no extension".to_string(), |e| {
+                format!("'.{}'", e.to_string_lossy())
+            });
+            error!("{}", ParseError::FileType(ext));
+            return;
+        }
+
+        if !in_date_range(f_path, config) {
+            return;
+        }
+        let catch_panics = config.catch_panics.unwrap_or(false);
+        let result = validate_game(
+            &mut Parser::new(),
+            f_path.to_path_buf(),
+            config,
+            catch_panics,
+        );
+        report();
+        match result {
+            Ok(game) => {
+                if let Some(db) = fingerprints.as_mut() {
+                    check_fingerprint(db, f_path, &game, config);
+                }
+            }
+            Err(e) => error!("Error during parsing: {e}"),
         }
+        save_fingerprints(fingerprints.as_ref(), config);
         return;
     } else if f_path.is_dir() {
         info!("Parsing directory {path}");
 
-        let files: Vec<PathBuf> = fs::read_dir(f_path)
+        let mut files: Vec<PathBuf> = fs::read_dir(f_path)
             .unwrap()
             .filter_map(|file| {
                 if let Ok(entry) = file {
                     let path = entry.path();
-                    if path.is_file() && path.extension().unwrap() == "slp" {
+                    if path.is_file() && (config.force || has_slp_extension(&path)) {
                         Some(path)
                     } else {
                         None
@@ -53,33 +133,238 @@ pub fn parse(path: &str) {
             })
             .collect();
 
+        // read_dir order depends on the filesystem, sort so output is reproducible across runs and machines
+        files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
         info!("Found {} files", files.len());
+        if config.matchups {
+            for (a, b, count) in matchup_histogram(&files) {
+                println!("{count:>6}  {a} vs {b}");
+            }
+            return;
+        }
+
+        let catch_panics = config.catch_panics.unwrap_or(true);
+        let mut parser = Parser::new();
+        let mut summary = DirectorySummary::default();
         for file in files {
+            if !in_date_range(&file, config) {
+                continue;
+            }
             println!("---");
-            info!("Validating {}", file.to_str().unwrap());
-            if let Err(e) = validate_game(file) {
+            info!("Validating {}", file.display());
+            let before = counts();
+            let result = validate_game(&mut parser, file.clone(), config, catch_panics);
+            if let (Ok(game), Some(db)) = (&result, fingerprints.as_mut()) {
+                check_fingerprint(db, &file, game, config);
+            }
+            let after = counts();
+            report();
+            summary.record(
+                result.as_ref().ok(),
+                after.errors - before.errors,
+                after.warnings - before.warnings,
+            );
+            if let Err(e) = result {
                 error!("Error during parsing: {e}");
             }
         }
+        println!("---");
+        summary.report();
+        save_fingerprints(fingerprints.as_ref(), config);
         return;
     }
     error!("invalid file path: {f_path:?}")
 }
 
+/// Records the game's fingerprint, or compares it against the recorded one, depending on
+/// `config.record_fingerprints`
+#[cfg(feature = "fs")]
+fn check_fingerprint(db: &mut FingerprintDb, path: &Path, game: &Game, config: &Config) {
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+    let fingerprint = game.fingerprint();
+
+    if config.record_fingerprints {
+        db.entries.insert(name, fingerprint);
+        return;
+    }
+    match db.entries.get(&name) {
+        Some(recorded) => {
+            let mismatches = recorded.mismatches(&fingerprint);
+            if !mismatches.is_empty() {
+                error!(
+                    "{name} doesn't match its recorded fingerprint, differs in: {}",
+                    mismatches.join(", ")
+                );
+            }
+        }
+        None => warn!("No fingerprint recorded for {name}"),
+    }
+}
+
+#[cfg(feature = "fs")]
+fn save_fingerprints(db: Option<&FingerprintDb>, config: &Config) {
+    let (Some(db), Some(path), true) = (db, &config.fingerprint_db, config.record_fingerprints)
+    else {
+        return;
+    };
+    match db.save(path) {
+        Ok(()) => info!(
+            "Recorded {} fingerprints to {}",
+            db.entries.len(),
+            path.display()
+        ),
+        Err(e) => error!("Unable to write fingerprints to {}: {e}", path.display()),
+    }
+}
+
+/// Totals over every replay validated in a directory run
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, Default)]
+struct DirectorySummary {
+    files: usize,
+    /// No errors or warnings
+    passed: usize,
+    /// Warnings, but no errors
+    with_warnings: usize,
+    /// Parsed, but with errors reported along the way
+    with_errors: usize,
+    /// Couldn't be parsed at all
+    failed: usize,
+    /// Every frame simulated, rollbacks included
+    simulated_frames: usize,
+    /// One per frame index
+    unique_frames: usize,
+}
+
+#[cfg(feature = "fs")]
+impl DirectorySummary {
+    fn record(&mut self, game: Option<&Game>, errors: usize, warnings: usize) {
+        self.files += 1;
+        let Some(game) = game else {
+            self.failed += 1;
+            return;
+        };
+        match (errors, warnings) {
+            (0, 0) => self.passed += 1,
+            (0, _) => self.with_warnings += 1,
+            _ => self.with_errors += 1,
+        }
+
+        self.simulated_frames += game.frame_starts.len();
+        self.unique_frames += game.frame_count();
+    }
+
+    fn report(&self) {
+        info!(
+            "Files: {}, Passed: {}, With warnings: {}, With errors: {}, Failed to parse: {}",
+            self.files, self.passed, self.with_warnings, self.with_errors, self.failed
+        );
+        if self.simulated_frames > 0 {
+            info!(
+                "Rollback %: {}",
+                self.simulated_frames.saturating_sub(self.unique_frames) as f32
+                    / self.simulated_frames as f32
+            );
+        }
+    }
+}
+
+/// Counts games per unordered character pair, most common first. Only the headers are read, and replays
+/// without exactly 2 players are skipped.
+#[cfg(feature = "fs")]
+pub fn matchup_histogram(files: &[PathBuf]) -> Vec<(String, String, usize)> {
+    let mut counts: HashMap<(String, String), usize> = HashMap::new();
+
+    for file in files {
+        let players = match parse_header(file) {
+            Ok((_, players, _)) => players,
+            Err(e) => {
+                error!("Error reading {}: {e}", file.display());
+                continue;
+            }
+        };
+        let mut chars: Vec<String> = players
+            .iter()
+            .filter(|p| p.player_type.has_frames())
+            .map(|p| p.character.to_string())
+            .collect();
+        if chars.len() != 2 {
+            continue;
+        }
+        chars.sort();
+        let b = chars.pop().unwrap();
+        let a = chars.pop().unwrap();
+        *counts.entry((a, b)).or_default() += 1;
+    }
+
+    let mut result: Vec<_> = counts
+        .into_iter()
+        .map(|((a, b), count)| (a, b, count))
+        .collect();
+    result.sort_by(|x, y| y.2.cmp(&x.2).then_with(|| (&x.0, &x.1).cmp(&(&y.0, &y.1))));
+
+    result
+}
+
+/// Checks the replay's start time against `config.since` and `config.until`. Only the header and metadata
+/// are read.
+#[cfg(feature = "fs")]
+fn in_date_range(path: &Path, config: &Config) -> bool {
+    if config.since.is_none() && config.until.is_none() {
+        return true;
+    }
+
+    let start_at = match parse_header(path) {
+        Ok((_, _, metadata)) => metadata.start_at,
+        Err(e) => {
+            error!("Error reading {}: {e}", path.display());
+            return false;
+        }
+    };
+    let Some(start_at) = start_at else {
+        info!("Skipping {}, no start time in metadata", path.display());
+        return false;
+    };
+
+    config.since.map_or(true, |since| start_at >= since)
+        && config.until.map_or(true, |until| start_at < until)
+}
+
+#[cfg(feature = "fs")]
+fn has_slp_extension(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "slp")
+}
+
+#[cfg(feature = "fs")]
 fn get_file_contents(path: &Path) -> Result<Bytes> {
     let mut f = File::open(path)?;
     let file_length = f.metadata()?.len() as usize;
     // #[cfg(debug_assertions)]
     // dbg!(file_length);
     let mut file_data = vec![0; file_length];
-    f.read_exact(&mut file_data).unwrap();
+    f.read_exact(&mut file_data)?;
+
+    Ok(Bytes::from(file_data))
+}
+
+#[cfg(feature = "fs")]
+fn get_stdin_contents() -> Result<Bytes> {
+    let mut file_data = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut file_data)?;
 
     Ok(Bytes::from(file_data))
 }
 
+/// Size of the data buffer in each MessageSplitter event
+const MESSAGE_SPLITTER_DATA: usize = 512;
+/// Data buffer, u16 used size, u8 internal command, bool last message
+const MESSAGE_SPLITTER_SIZE: usize = MESSAGE_SPLITTER_DATA + 4;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr, Default, Hash)]
 #[repr(u8)]
-enum EventType {
+pub(crate) enum EventType {
     EventPayloads = 0x35,
     GameStart = 0x36,
     PreFrame = 0x37,
@@ -94,336 +379,1468 @@ enum EventType {
     None = 0x00,
 }
 
-fn get_event_sizes(file: &mut Bytes) -> Result<HashMap<EventType, u16>> {
-    let code = EventType::from_repr(file.get_u8()).unwrap();
+/// Reads the EventPayloads table. Events this parser doesn't know about go in `unknown_map`, keyed by their
+/// command byte, so they can still be skipped over by their declared size.
+fn get_event_sizes(
+    file: &mut Bytes,
+    offset: usize,
+    event_map: &mut HashMap<EventType, u16>,
+    unknown_map: &mut HashMap<u8, u16>,
+) -> Result<()> {
     ensure!(
-        code == EventType::EventPayloads,
-        ParseError::Value(
-            format!("{:?}", EventType::EventPayloads),
-            format!("{:?}", code)
-        )
+        file.remaining() >= 2,
+        ParseError::CorruptStream {
+            offset,
+            context: "File ends before EventPayloads".to_string(),
+        }
+    );
+    let code = file.get_u8();
+    ensure!(
+        code == EventType::EventPayloads as u8,
+        ParseError::CorruptStream {
+            offset,
+            context: format!("Expected {:?}, got {:#04X}", EventType::EventPayloads, code),
+        }
     );
 
     let payloads_size = file.get_u8();
 
     ensure!(
-        (payloads_size - 1) % 3 == 0,
-        anyhow!("EventPayloads length invalid")
+        payloads_size > 0
+            && (payloads_size - 1) % 3 == 0
+            && file.remaining() >= (payloads_size - 1) as usize,
+        ParseError::CorruptStream {
+            offset: offset + 1,
+            context: format!("EventPayloads length invalid: {payloads_size}"),
+        }
     );
 
-    let mut event_map = HashMap::default();
+    event_map.clear();
+    unknown_map.clear();
 
     for _ in (0..(payloads_size - 1)).step_by(3) {
-        let event = EventType::from_repr(file.get_u8()).unwrap();
+        let code = file.get_u8();
         let size = file.get_u16();
-        event_map.insert(event, size);
+        match EventType::from_repr(code) {
+            Some(event) => {
+                event_map.insert(event, size);
+            }
+            None => {
+                warn!("Unknown event type 0x{code:02X} declared in EventPayloads with size {size}");
+                unknown_map.insert(code, size);
+            }
+        }
     }
 
-    Ok(event_map)
+    Ok(())
 }
 
-fn expect_bytes(stream: &mut Bytes, expected: &[u8], message: &str) -> std::io::Result<()> {
-    let actual = stream.get(0..expected.len()).unwrap();
-    if expected == actual {
-        stream.advance(expected.len());
-        Ok(())
-    } else {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!("Expected {message}: {expected:?}, got {actual:?}"),
-        ))
+fn dump_payloads(event_sizes: &HashMap<EventType, u16>) {
+    let mut sizes: Vec<_> = event_sizes.iter().collect();
+    sizes.sort_by_key(|(event, _)| **event as u8);
+
+    info!("EventPayloads ({} events):", sizes.len());
+    for (event, size) in sizes {
+        info!("    0x{:02X} {:?}: {} bytes", *event as u8, event, size);
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Expected {
-    port: Port,
-    nana: bool,
-    kind: EventType,
-}
+/// `{U\x03raw[$U#l`, followed by the u32 length of the raw element
+const SLP_HEADER: [u8; 11] = [
+    0x7b, 0x55, 0x03, 0x72, 0x61, 0x77, 0x5b, 0x24, 0x55, 0x23, 0x6c,
+];
 
-fn validate_game(path: PathBuf) -> Result<()> {
-    let file_data = get_file_contents(&path)?;
-    let mut stream = file_data.slice(..);
+/// `metadata` key & type ("U\x08metadata{")
+const METADATA_HEADER: [u8; 11] = [
+    0x55, 0x08, 0x6d, 0x65, 0x74, 0x61, 0x64, 0x61, 0x74, 0x61, 0x7b,
+];
 
-    expect_bytes(
-        &mut stream,
-        &[
-            0x7b, 0x55, 0x03, 0x72, 0x61, 0x77, 0x5b, 0x24, 0x55, 0x23, 0x6c,
-        ],
-        "Slippi header",
-    )?;
+/// Parses the UBJSON metadata block. `offset` is the block's position in the file, for error reporting.
+fn parse_metadata(mut stream: Bytes, offset: usize) -> Result<Metadata> {
+    expect_bytes(&mut stream, &METADATA_HEADER, "metadata header")
+        .map_err(|context| ParseError::CorruptStream { offset, context })?;
+
+    let block_len = stream.len();
+    let mut reader = stream.reader();
+    let map = ubjson::to_map(&mut reader).map_err(|e| at_offset(offset, e))?;
+
+    // only the top level object's closing brace should be left
+    let rest = reader.into_inner();
+    let end = offset + METADATA_HEADER.len() + block_len - rest.len();
+    match rest.remaining() {
+        1 if rest[0] == 0x7d => (),
+        0 => warn!("[File pos: {end}] File ends without closing the top level object"),
+        n => warn!("[File pos: {end}] {n} bytes of unexpected data after the metadata block"),
+    }
+
+    Ok(Metadata::from_map(map))
+}
 
+/// Reads only the header, GameStart event, and metadata block of a replay. The frame stream is never
+/// read, so this is near-instant regardless of the replay's length.
+#[cfg(feature = "fs")]
+pub fn parse_header(path: &Path) -> Result<(GameStart, [Player; 4], Metadata)> {
+    let mut f = File::open(path)?;
+
+    // header, raw length, EventPayloads command byte and size
+    let mut buf = vec![0; SLP_HEADER.len() + 6];
+    f.read_exact(&mut buf)?;
+    let mut stream = Bytes::from(buf.clone());
+    expect_bytes(&mut stream, &SLP_HEADER, "Slippi header").map_err(ParseError::NotSlp)?;
     let raw_length = stream.get_u32() as u64 + 15;
-    trace!("Raw length: {raw_length}");
 
-    // ----------------------------------- metadata block ----------------------------------- //
-    let mut temp_meta = file_data.slice(raw_length as usize..);
+    // the EventPayloads size byte counts itself
+    let payloads_size = (stream[1] as usize).saturating_sub(1);
+    buf.resize(buf.len() + payloads_size, 0);
+    f.read_exact(&mut buf[SLP_HEADER.len() + 6..])?;
 
-    expect_bytes(
-        &mut temp_meta,
-        // `metadata` key & type ("U\x08metadata{")
-        &[
-            0x55, 0x08, 0x6d, 0x65, 0x74, 0x61, 0x64, 0x61, 0x74, 0x61, 0x7b,
-        ],
-        "metadata header",
+    let mut event_sizes = HashMap::new();
+    let mut stream = Bytes::from(buf).slice(SLP_HEADER.len() + 4..);
+    get_event_sizes(
+        &mut stream,
+        SLP_HEADER.len() + 4,
+        &mut event_sizes,
+        &mut HashMap::new(),
     )?;
 
-    let mut frame_count: usize = 0;
+    let offset = SLP_HEADER.len() + 6 + payloads_size;
+    let start_size = event_size(&event_sizes, EventType::GameStart, offset)?;
+    let mut raw_start = vec![0; start_size + 1];
+    f.read_exact(&mut raw_start)?;
+    let mut raw_start = Bytes::from(raw_start);
+    expect_bytes(
+        &mut raw_start,
+        &[EventType::GameStart as u8],
+        "game start command byte",
+    )
+    .map_err(|context| ParseError::CorruptStream { offset, context })?;
+    let (game_start, _, players) = GameStart::parse(raw_start).map_err(|e| at_offset(offset, e))?;
 
-    let metadata = ubjson::to_map(&mut temp_meta.reader())?;
-    if let serde_json::Value::Number(lastframe) = &metadata["lastFrame"] {
-        // duration, in frames, is translated to seconds. 123 is subtracted from the frame count
-        // to match the duration to the in-game timer. The total frame count is easily
-        // found from player.frames.len()
-        let last = lastframe.as_i64().unwrap();
-        frame_count = (last + 124) as usize;
-    };
+    f.seek(SeekFrom::Start(raw_length))?;
+    let mut meta = Vec::new();
+    f.read_to_end(&mut meta)?;
+    let metadata = parse_metadata(Bytes::from(meta), raw_length as usize)?;
 
-    trace!("Frame count: {frame_count}");
+    Ok((game_start, players, metadata))
+}
 
-    if let serde_json::Value::String(start_at) = &metadata["startAt"] {
-        trace!("Date: {start_at}")
+/// The version to parse `event` as. Normally this is just the replay's version, but files re-encoded by other
+/// tools can declare smaller events than their version implies. In that case the newest version whose fields
+/// fit in the declared size is used, so the parsers stop before running out of bytes.
+fn fitting_version(
+    event_sizes: &HashMap<EventType, u16>,
+    event: EventType,
+    version: Version,
+    expected_size: fn(Version) -> usize,
+) -> Version {
+    let Some(&declared) = event_sizes.get(&event) else {
+        return version;
+    };
+    let declared = declared as usize;
+    let expected = expected_size(version);
+    if declared >= expected {
+        return version;
     }
 
-    let event_sizes = get_event_sizes(&mut stream)?;
+    let fitting = SPEC_VERSIONS
+        .iter()
+        .rev()
+        .copied()
+        .filter(|v| *v <= version)
+        .find(|v| expected_size(*v) <= declared)
+        .unwrap_or(version);
+    warn!("{event:?} events are declared as {declared} bytes, but {version} expects {expected}. Reading them as {fitting}");
 
-    expect_bytes(
-        &mut stream,
-        &[EventType::GameStart as u8],
-        "game start command byte",
-    )?;
+    fitting
+}
 
-    let raw_start = stream.slice(0..event_sizes[&EventType::GameStart] as usize);
-    stream.advance(event_sizes[&EventType::GameStart] as usize);
+/// Finds the next byte that looks like the start of a FrameStart event: the command byte followed by a frame
+/// index close enough to `last_frame` to be a normal step forward or a rollback. Returns its position in
+/// `stream`.
+fn find_frame_start(stream: &Bytes, last_frame: i32) -> Option<usize> {
+    let plausible = (last_frame as i64 - MAX_ROLLBACK as i64)..=(last_frame as i64 + 2);
+    stream.windows(5).position(|w| {
+        w[0] == EventType::FrameStart as u8
+            && plausible.contains(&(i32::from_be_bytes([w[1], w[2], w[3], w[4]]) as i64))
+    })
+}
 
-    let (game_start, version, mut players) = GameStart::parse(raw_start)?;
+/// Attaches the file offset of the event being parsed to an error from one of the event constructors. Errors that
+/// already carry an offset are left alone.
+fn at_offset(offset: usize, e: anyhow::Error) -> ParseError {
+    match e.downcast::<ParseError>() {
+        Ok(e @ ParseError::CorruptStream { .. }) => e,
+        Ok(e) => ParseError::CorruptStream {
+            offset,
+            context: e.to_string(),
+        },
+        Err(e) => ParseError::CorruptStream {
+            offset,
+            context: e.to_string(),
+        },
+    }
+}
 
-    info!("Parser max version: 3.16.0, Replay version: {version}");
+/// With `Config::fail_fast`, turns the first error reported for this replay into a hard failure. Runs after every
+/// event, so the message is only looked up once there is one.
+#[inline]
+fn fail_fast(config: &Config) -> Result<()> {
+    if !config.fail_fast || !has_errors() {
+        return Ok(());
+    }
+    match first_error() {
+        Some(message) => Err(ParseError::Validation(message).into()),
+        None => Ok(()),
+    }
+}
 
-    let mut event = EventType::None;
-    let mut pos = file_data.len() - stream.len();
+/// Walking the event stream should consume exactly the raw length declared in the header. If it doesn't, either
+/// an event size is wrong or the header is.
+fn check_raw_length(end: usize, raw_length: usize, last_event: EventType) {
+    match end.cmp(&raw_length) {
+        Ordering::Equal => (),
+        Ordering::Less if last_event == EventType::GameEnd => warn!(
+            "[File pos: {end}] {} bytes of events after GameEnd, before the declared end of the raw element",
+            raw_length - end
+        ),
+        Ordering::Less => warn!(
+            "[File pos: {end}] Event stream ended {} bytes before the declared end of the raw element",
+            raw_length - end
+        ),
+        Ordering::Greater => warn!(
+            "[File pos: {end}] Event stream ran {} bytes past the declared end of the raw element",
+            end - raw_length
+        ),
+    }
+}
 
-    let mut fstart = FrameStart::default();
-    // tiny workaround due to debug rollback check
-    fstart.frame_idx = -123;
-    let mut pre = PreFrame::default();
-    let mut post = PostFrame::default();
-    let mut fend = FrameEnd::default();
-    let mut item = ItemFrame::default();
-    let mut game_end = None;
+/// Looks up the declared size of an event, erroring if the EventPayloads table didn't declare it
+fn event_size(
+    event_sizes: &HashMap<EventType, u16>,
+    event: EventType,
+    offset: usize,
+) -> Result<usize> {
+    event_sizes.get(&event).map(|s| *s as usize).ok_or_else(|| {
+        ParseError::CorruptStream {
+            offset,
+            context: format!("{event:?} event not declared in EventPayloads"),
+        }
+        .into()
+    })
+}
 
-    let mut event_order = vec![Expected {
-        port: Port::P1,
-        nana: false,
-        kind: EventType::FrameStart,
-    }];
-
-    for player in &players {
-        if matches!(player.player_type, PlayerType::CPU | PlayerType::Human) {
-            event_order.push(Expected {
-                port: player.port,
-                nana: false,
-                kind: EventType::PreFrame,
-            });
-            if player.character == Character::IceClimbers {
-                event_order.push(Expected {
-                    port: player.port,
-                    nana: true,
-                    kind: EventType::PreFrame,
-                });
+/// Looks up the declared size of an event type this parser doesn't know, see [`get_event_sizes`]
+fn unknown_event_size(unknown_sizes: &HashMap<u8, u16>, code: u8, offset: usize) -> Result<usize> {
+    unknown_sizes
+        .get(&code)
+        .map(|s| *s as usize)
+        .ok_or_else(|| {
+            ParseError::CorruptStream {
+                offset,
+                context: format!("Unknown event type 0x{code:02X} not declared in EventPayloads"),
             }
+            .into()
+        })
+}
+
+/// Consumes `expected` from the front of the stream. On mismatch, returns a description of what was
+/// found instead so the caller can wrap it in the appropriate `ParseError`.
+fn expect_bytes(
+    stream: &mut Bytes,
+    expected: &[u8],
+    message: &str,
+) -> std::result::Result<(), String> {
+    match stream.get(0..expected.len()) {
+        Some(actual) if actual == expected => {
+            stream.advance(expected.len());
+            Ok(())
         }
+        Some(actual) => Err(format!("Expected {message}: {expected:?}, got {actual:?}")),
+        None => Err(format!("Expected {message}: {expected:?}, got end of file")),
     }
+}
 
-    event_order.push(Expected {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expected {
+    port: Port,
+    nana: bool,
+    kind: EventType,
+}
+
+/// Fills `event_order` with the events every frame should contain, in order. Ice Climbers get a second entry
+/// for Nana right after each of Popo's, as long as `nana_present` says she's still around.
+fn build_event_order(
+    event_order: &mut Vec<Expected>,
+    players: &[Player; 4],
+    nana_present: &[bool; 4],
+) {
+    let frame_event = |kind: EventType| Expected {
         port: Port::P1,
         nana: false,
-        kind: EventType::Item,
-    });
-
-    for player in &players {
-        if matches!(player.player_type, PlayerType::CPU | PlayerType::Human) {
-            event_order.push(Expected {
-                port: player.port,
-                nana: false,
-                kind: EventType::PostFrame,
-            });
-            if player.character == Character::IceClimbers {
-                event_order.push(Expected {
-                    port: player.port,
+        kind,
+    };
+    let player_events = |kind: EventType| {
+        players
+            .iter()
+            .filter(|p| p.player_type.has_frames())
+            .flat_map(move |p| {
+                let leader = Expected {
+                    port: p.port,
                     nana: false,
-                    kind: EventType::PostFrame,
+                    kind,
+                };
+                let has_nana =
+                    p.character == Character::IceClimbers && nana_present[p.port as usize];
+                let nana = has_nana.then_some(Expected {
+                    port: p.port,
+                    nana: true,
+                    kind,
                 });
-            }
+                std::iter::once(leader).chain(nana)
+            })
+    };
+
+    event_order.clear();
+    event_order.push(frame_event(EventType::FrameStart));
+    event_order.extend(player_events(EventType::PreFrame));
+    event_order.push(frame_event(EventType::Item));
+    event_order.extend(player_events(EventType::PostFrame));
+    event_order.push(frame_event(EventType::FrameEnd));
+}
+
+/// Checks `got` against the expected event at `order_idx`. Items (there may be none this frame) and the events
+/// of a Nana in the middle of dying aren't guaranteed to appear, so their slots are skipped over when they don't
+/// match. On a match, `order_idx` is moved to the matching slot.
+fn match_expected(
+    event_order: &[Expected],
+    order_idx: &mut usize,
+    got: &Expected,
+    nana_dying: &[bool; 4],
+) -> bool {
+    let mut i = *order_idx;
+    while let Some(exp) = event_order.get(i) {
+        if exp == got {
+            *order_idx = i;
+            return true;
+        }
+        let optional = exp.kind == EventType::Item || (exp.nana && nana_dying[exp.port as usize]);
+        if !optional {
+            break;
         }
+        i += 1;
     }
 
-    event_order.push(Expected {
-        port: Port::P1,
-        nana: false,
-        kind: EventType::FrameEnd,
-    });
-    let mut order_idx = 0;
-    let mut need_sync = false;
-
-    let mut actual_frames = 0;
-
-    // --------------------------------------- Parse loop --------------------------------------- //
-    while pos < raw_length as usize && event != EventType::GameEnd && stream.has_remaining() {
-        let code = stream.get_u8();
-        event = EventType::from_repr(code).unwrap_or_default();
-        /* EventType::None allows the parser to continue working on newer replays (with possible
-        new events). During testing all events must be accounted for, so any EventType::Nones
-        are likely a misalignment of my slices */
-        if event == EventType::None {
-            warn!("Unknown event type: {code}");
-        }
-        let size = event_sizes[&event] as usize;
-
-        match event {
-            EventType::FrameStart => {
-                actual_frames += 1;
-
-                let old_frame = fstart.frame_idx;
-                fstart = FrameStart::new(stream.slice(..size), version);
-                let exp_event = &event_order[order_idx];
-                if need_sync || (exp_event.kind != EventType::FrameStart && !exp_event.nana) {
-                    let expected = match exp_event.kind {
-                        EventType::Item => "EventType::Item or EventType::PostFrame".to_owned(),
-                        x => format!("EventType::{:?}", x),
+    false
+}
+
+/// True if GameStart marks the port as empty. The first time that happens for a port, the disagreement between
+/// GameStart and the frame data is reported.
+fn check_empty_port(
+    players: &[Player; 4],
+    empty_reported: &mut [bool; 4],
+    port: Port,
+    pos: usize,
+) -> bool {
+    if players[port as usize].player_type != PlayerType::Empty {
+        return false;
+    }
+    if !empty_reported[port as usize] {
+        empty_reported[port as usize] = true;
+        error!("[File pos: {pos}] Frame events for {port}, which GameStart marks as empty");
+    }
+
+    true
+}
+
+/// Called for every Nana event. If her events had stopped (she died), they're expected again from here on and
+/// `order_idx` is moved to her slot.
+fn nana_respawned(
+    event_order: &mut Vec<Expected>,
+    order_idx: &mut usize,
+    players: &[Player; 4],
+    nana_present: &mut [bool; 4],
+    got: &Expected,
+) {
+    let i = got.port as usize;
+    // Nana events for anyone but the Ice Climbers are already reported by the frame parsers
+    if nana_present[i] || players[i].character != Character::IceClimbers {
+        return;
+    }
+
+    nana_present[i] = true;
+    build_event_order(event_order, players, nana_present);
+    if let Some(idx) = event_order.iter().position(|e| e == got) {
+        *order_idx = idx;
+    }
+}
+
+/// Called at every FrameEnd. A Nana that sent no events this frame is gone until she respawns with Popo, so her
+/// slots are removed from `event_order`.
+fn drop_departed_nanas(
+    event_order: &mut Vec<Expected>,
+    players: &[Player; 4],
+    nana_present: &mut [bool; 4],
+    nana_dying: &mut [bool; 4],
+    seen_nana: [bool; 4],
+    pos: usize,
+    frame_idx: i32,
+) {
+    let mut nana_left = false;
+    for player in players
+        .iter()
+        .filter(|p| p.character == Character::IceClimbers)
+    {
+        let i = player.port as usize;
+        if !player.player_type.has_frames() || !nana_present[i] || seen_nana[i] {
+            continue;
+        }
+        if !nana_dying[i] {
+            warn!(
+                "[File pos: {}] Nana's events stopped for {} on frame {} without her dying",
+                pos, player.port, frame_idx
+            );
+        }
+        nana_present[i] = false;
+        nana_dying[i] = false;
+        nana_left = true;
+    }
+    if nana_left {
+        build_event_order(event_order, players, nana_present);
+    }
+}
+
+#[cfg(feature = "fs")]
+fn validate_game(
+    parser: &mut Parser,
+    path: PathBuf,
+    config: &Config,
+    catch_panics: bool,
+) -> Result<Game> {
+    let file_data = get_file_contents(&path)?;
+    if !catch_panics {
+        return parser.validate_bytes(file_data, config);
+    }
+
+    // the parser's buffers are cleared at the start of every replay, so a panic can't leave it in a state that
+    // affects the next one
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        parser.validate_bytes(file_data, config)
+    }))
+    .unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err(ParseError::Panic(message).into())
+    })
+}
+
+/// Reusable parser state. The event size table and expected event ordering are cleared rather than
+/// reallocated between replays, so validating many files in a row doesn't allocate them per file.
+#[derive(Debug, Default)]
+pub struct Parser {
+    event_sizes: HashMap<EventType, u16>,
+    unknown_sizes: HashMap<u8, u16>,
+    event_order: Vec<Expected>,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validates a full replay that has already been read into memory
+    pub fn validate_bytes(&mut self, file_data: Bytes, config: &Config) -> Result<Game> {
+        let _sink = SinkGuard::set(config.sink.clone());
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+
+        let result = self.validate_events(file_data, config);
+
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &config.metrics {
+            // the guard above is fresh, so these only cover this replay
+            let counts = counts();
+            metrics.record(&FileMetrics::new(
+                result.as_ref().ok(),
+                start.elapsed(),
+                counts.errors,
+                counts.warnings,
+            ));
+        }
+
+        result
+    }
+
+    fn validate_events(&mut self, file_data: Bytes, config: &Config) -> Result<Game> {
+        let mut stream = file_data.slice(..);
+
+        expect_bytes(&mut stream, &SLP_HEADER, "Slippi header").map_err(ParseError::NotSlp)?;
+
+        ensure!(
+            stream.remaining() >= 4,
+            ParseError::CorruptStream {
+                offset: file_data.len() - stream.len(),
+                context: "File ends before raw length".to_string(),
+            }
+        );
+        let declared_length = stream.get_u32();
+        // replays that are still being written (or were never closed) have a raw length of 0 and no metadata
+        let in_progress = declared_length == 0;
+        let raw_length = if in_progress {
+            info!("Replay was never finalized, reading events until the end of the file");
+            file_data.len() as u64
+        } else {
+            declared_length as u64 + 15
+        };
+        trace!("Raw length: {raw_length}");
+        ensure!(
+            raw_length <= file_data.len() as u64,
+            ParseError::CorruptStream {
+                offset: 11,
+                context: format!(
+                    "Raw length {raw_length} exceeds file length {}",
+                    file_data.len()
+                ),
+            }
+        );
+
+        // ----------------------------------- metadata block ----------------------------------- //
+        let mut frame_count: usize = 0;
+
+        let metadata = if in_progress {
+            Metadata::default()
+        } else {
+            parse_metadata(file_data.slice(raw_length as usize..), raw_length as usize)?
+        };
+        if let Some(last) = metadata.last_frame {
+            // duration, in frames, is translated to seconds. 123 is subtracted from the frame count
+            // to match the duration to the in-game timer. The total frame count is easily
+            // found from player.frames.len()
+            frame_count = (last - FIRST_FRAME as i64 + 1) as usize;
+        };
+
+        trace!("Frame count: {frame_count}");
+
+        if let Some(start_at) = metadata.start_at {
+            trace!("Date: {start_at}")
+        }
+
+        let payloads_start = file_data.len() - stream.len();
+        get_event_sizes(
+            &mut stream,
+            payloads_start,
+            &mut self.event_sizes,
+            &mut self.unknown_sizes,
+        )?;
+        let payloads_end = file_data.len() - stream.len();
+
+        let mut raw_events = vec![RawEvent {
+            code: EventType::EventPayloads as u8,
+            payload: file_data.slice(payloads_start + 1..payloads_end),
+        }];
+        let event_sizes = &self.event_sizes;
+        let unknown_sizes = &self.unknown_sizes;
+
+        if config.dump_payloads {
+            dump_payloads(event_sizes);
+        }
+
+        let offset = file_data.len() - stream.len();
+        expect_bytes(
+            &mut stream,
+            &[EventType::GameStart as u8],
+            "game start command byte",
+        )
+        .map_err(|context| ParseError::CorruptStream { offset, context })?;
+
+        let start_size = event_size(event_sizes, EventType::GameStart, offset)?;
+        ensure!(
+            stream.remaining() >= start_size,
+            ParseError::CorruptStream {
+                offset,
+                context: "File ends mid GameStart event".to_string(),
+            }
+        );
+        let raw_start = stream.slice(0..start_size);
+        stream.advance(start_size);
+        raw_events.push(RawEvent {
+            code: EventType::GameStart as u8,
+            payload: raw_start.clone(),
+        });
+
+        let (game_start, version, mut players) =
+            GameStart::parse(raw_start).map_err(|e| at_offset(offset, e))?;
+
+        info!("Parser max version: 3.16.0, Replay version: {version}");
+
+        let start_version = fitting_version(
+            event_sizes,
+            EventType::FrameStart,
+            version,
+            FrameStart::expected_size,
+        );
+        let pre_version = fitting_version(
+            event_sizes,
+            EventType::PreFrame,
+            version,
+            PreFrame::expected_size,
+        );
+        let post_version = fitting_version(
+            event_sizes,
+            EventType::PostFrame,
+            version,
+            PostFrame::expected_size,
+        );
+        let item_version = fitting_version(
+            event_sizes,
+            EventType::Item,
+            version,
+            ItemFrame::expected_size,
+        );
+        let end_version = fitting_version(
+            event_sizes,
+            EventType::FrameEnd,
+            version,
+            FrameEnd::expected_size,
+        );
+
+        let humans = players
+            .iter()
+            .filter(|p| p.player_type == PlayerType::Human)
+            .count();
+        if humans != 2 {
+            ensure!(!config.strict, ParseError::PlayerCount);
+            // training mode is always 1 human and however many CPU dummies
+            if game_start.mode == Mode::Training {
+                info!("Training mode replay with {humans} human players");
+            } else {
+                warn!("Replay has {humans} human players");
+            }
+        }
+
+        match game_start.legality() {
+            Legality::Legal => (),
+            Legality::Illegal(reason) => {
+                ensure!(!config.strict, ParseError::Illegal(reason));
+                warn!("{reason}");
+            }
+            Legality::Undetermined(reason) => info!("{reason}"),
+        }
+        fail_fast(config)?;
+
+        let mut event = EventType::None;
+        let mut pos = file_data.len() - stream.len();
+
+        let mut fstart = FrameStart::default();
+        // makes the first frame look like a normal step forward to the frame ordering check
+        fstart.frame_idx = FIRST_FRAME - 1;
+        let mut pre = PreFrame::default();
+        let mut post = PostFrame::default();
+        let mut fend = FrameEnd::default();
+        let mut item = ItemFrame::default();
+        let mut game_end = None;
+
+        let mut frame_starts = Vec::new();
+        let mut frame_ends = Vec::new();
+        let mut pre_frames = Vec::new();
+        let mut post_frames = Vec::new();
+        let mut item_frames = Vec::new();
+
+        let event_order = &mut self.event_order;
+        // Slippi stops sending Nana's events once she dies, and starts again when she respawns with Popo
+        let mut nana_present = [true; 4];
+        let mut nana_dying = [false; 4];
+        build_event_order(event_order, &players, &nana_present);
+        let mut order_idx = 0;
+        let mut need_sync = false;
+        // without FrameEnd (pre v3.0.0) there's nothing marking where a frame ends, so ordering can't be checked
+        let check_order = event_sizes.contains_key(&EventType::FrameEnd);
+        // without FrameStart (pre v2.2.0) there's no frame to compare the Pre/PostFrame indices to
+        let has_frame_start = event_sizes.contains_key(&EventType::FrameStart);
+        // which pre/post frames were seen this frame, indexed by port and then by nana
+        let mut seen_pre = [[false; 2]; 4];
+        let mut seen_post = [[false; 2]; 4];
+        // ports that GameStart marks as empty but that have frame events anyway, only reported once each
+        let mut empty_reported = [false; 4];
+        let mut frame_items = 0;
+        let mut gecko_len = 0;
+
+        let mut actual_frames = 0;
+        // set when the loop bails out before the end of the raw element, the length check doesn't apply then
+        let mut stopped_early = false;
+
+        // --------------------------------------- Parse loop --------------------------------------- //
+        while pos < raw_length as usize && event != EventType::GameEnd && stream.has_remaining() {
+            let code = stream.get_u8();
+            event = EventType::from_repr(code).unwrap_or_default();
+            /* EventType::None allows the parser to continue working on newer replays (with possible
+            new events). Those are skipped by the size EventPayloads declares for their code, an
+            undeclared code is likely a misalignment of my slices */
+            let declared = if event == EventType::None {
+                unknown_event_size(unknown_sizes, code, pos)
+            } else {
+                event_size(event_sizes, event, pos)
+            };
+            let size = match declared {
+                Ok(size) => size,
+                Err(e) if config.repair => {
+                    let Some(skip) = find_frame_start(&stream, fstart.frame_idx) else {
+                        warn!("[File pos: {pos}] {e}. No FrameStart found to resync to, ignoring the rest of the event stream");
+                        stopped_early = true;
+                        break;
                     };
-                    error!("[File pos: {}] Unexpected event ordering. Expected {} for frame {}, got EventType::FrameStart for frame {}", pos, expected, old_frame, fstart.frame_idx);
+                    warn!(
+                        "[File pos: {pos}] {e}. Skipping {} bytes to resync at the next FrameStart",
+                        skip + 1
+                    );
+                    stream.advance(skip);
+                    pos = file_data.len() - stream.len();
                     order_idx = 0;
                     need_sync = false;
+                    continue;
                 }
-                if fstart.frame_idx - old_frame > 1 || fstart.frame_idx - old_frame < -10 {
-                    error!("[File pos: {}] Unexpected frame ordering. Previous frame was index {}, current frame is index {}", pos, old_frame, fstart.frame_idx);
-                }
-                if fstart.frame_idx < old_frame {
-                    debug!(
-                        "[File pos: {}] Rollback from frame {} to frame {}",
-                        pos, old_frame, fstart.frame_idx
-                    );
-                }
-                order_idx += 1;
+                Err(e) => return Err(e),
+            };
+            // the command byte at `pos` has already been read
+            if stream.remaining() < size || pos + 1 + size > raw_length as usize {
+                // usually a game that crashed or was closed mid-write, keep everything up to this point
+                warn!("[File pos: {pos}] Replay truncated mid {event:?} event, ignoring the rest of the event stream");
+                stopped_early = true;
+                break;
             }
-            EventType::PreFrame => {
-                pre = PreFrame::new(stream.slice(..size), version, &players);
-                let exp_event = &event_order[order_idx];
-                let got_event = Expected {
-                    port: Port::from_repr(pre.port).unwrap(),
-                    nana: pre.nana,
-                    kind: EventType::PreFrame,
-                };
+            raw_events.push(RawEvent {
+                code,
+                payload: stream.slice(..size),
+            });
 
-                let mut not_exp = *exp_event != got_event;
+            match event {
+                EventType::FrameStart => {
+                    actual_frames += 1;
+                    frame_items = 0;
 
-                if not_exp && exp_event.nana && event_order[order_idx + 1] == got_event {
+                    let old_frame = fstart.frame_idx;
+                    fstart = FrameStart::new(stream.slice(..size), start_version)
+                        .map_err(|e| at_offset(pos, e))?;
+                    fstart.offset = pos;
+                    seen_pre = [[false; 2]; 4];
+                    seen_post = [[false; 2]; 4];
+                    let exp_event = &event_order[order_idx];
+                    // a desync was already reported, FrameStart is where the ordering picks back up
+                    if check_order
+                        && !need_sync
+                        && exp_event.kind != EventType::FrameStart
+                        && !exp_event.nana
+                    {
+                        let expected = match exp_event.kind {
+                            EventType::Item => "EventType::Item or EventType::PostFrame".to_owned(),
+                            x => format!("EventType::{:?}", x),
+                        };
+                        error!("[File pos: {}] Unexpected event ordering. Expected {} for frame {}, got EventType::FrameStart for frame {}", pos, expected, old_frame, fstart.frame_idx);
+                    }
+                    order_idx = 0;
+                    need_sync = false;
+                    if frame_starts.is_empty() && fstart.frame_idx != FIRST_FRAME {
+                        warn!(
+                            "[File pos: {}] First frame is index {}, expected {}",
+                            pos, fstart.frame_idx, FIRST_FRAME
+                        );
+                    }
+                    if fstart.frame_idx < FIRST_FRAME {
+                        error!(
+                            "[File pos: {}] Frame index {} is before the first frame ({})",
+                            pos, fstart.frame_idx, FIRST_FRAME
+                        );
+                    }
+                    let delta = fstart.frame_idx as i64 - old_frame as i64;
+                    if delta > 1 || delta < -(MAX_ROLLBACK as i64) {
+                        error!("[File pos: {}] Unexpected frame ordering. Previous frame was index {}, current frame is index {}", pos, old_frame, fstart.frame_idx);
+                    }
+                    if fstart.frame_idx < old_frame {
+                        debug!(
+                            "[File pos: {}] Rollback from frame {} to frame {}",
+                            pos, old_frame, fstart.frame_idx
+                        );
+                    }
+                    frame_starts.push(fstart);
                     order_idx += 1;
-                    not_exp = false;
                 }
-
-                if !need_sync && not_exp && players.len() == 2 {
-                    need_sync = true;
-                    let expected = match exp_event.kind {
-                        EventType::Item => "EventType::Item or EventType::PostFrame".to_owned(),
-                        x => format!("{:?}", x),
+                EventType::PreFrame => {
+                    pre = PreFrame::new(stream.slice(..size), pre_version, &players, config)
+                        .map_err(|e| at_offset(pos, e))?;
+                    pre.offset = pos;
+                    seen_pre[pre.port as usize][pre.nana as usize] = true;
+                    let got_event = Expected {
+                        port: Port::from_repr(pre.port).unwrap(),
+                        nana: pre.nana,
+                        kind: EventType::PreFrame,
                     };
-                    error!("[File pos: {}] Unexpected event ordering. Expected {} for frame {}, got EventType::PreFrame for frame {}", pos, expected, fstart.frame_idx, pre.frame_index)
+                    // not part of the expected order at all, checking it would only report a desync
+                    let from_empty =
+                        check_empty_port(&players, &mut empty_reported, got_event.port, pos);
+
+                    if got_event.nana && !from_empty {
+                        nana_respawned(
+                            event_order,
+                            &mut order_idx,
+                            &players,
+                            &mut nana_present,
+                            &got_event,
+                        );
+                    }
+                    let not_exp = !from_empty
+                        && !match_expected(event_order, &mut order_idx, &got_event, &nana_dying);
+
+                    if check_order && !need_sync && not_exp {
+                        need_sync = true;
+                        let expected = match event_order[order_idx].kind {
+                            EventType::Item => "EventType::Item or EventType::PostFrame".to_owned(),
+                            x => format!("{:?}", x),
+                        };
+                        error!("[File pos: {}] Unexpected event ordering. Expected {} for frame {}, got EventType::PreFrame for frame {}", pos, expected, fstart.frame_idx, pre.frame_index)
+                    }
+
+                    if has_frame_start && pre.frame_index != fstart.frame_idx {
+                        error!(
+                            "[File pos: {}] PreFrame for frame {} inside frame {}",
+                            pos, pre.frame_index, fstart.frame_idx
+                        );
+                    }
+
+                    pre_frames.push(pre.clone());
+                    if !from_empty {
+                        order_idx += 1;
+                    }
                 }
+                EventType::PostFrame => {
+                    post = PostFrame::new(stream.slice(..size), post_version, config)
+                        .map_err(|e| at_offset(pos, e))?;
+                    post.offset = pos;
+                    seen_post[post.port as usize][post.nana as usize] = true;
+                    let got_event = Expected {
+                        port: Port::from_repr(post.port).unwrap(),
+                        nana: post.nana,
+                        kind: EventType::PostFrame,
+                    };
+                    // not part of the expected order at all, checking it would only report a desync
+                    let from_empty =
+                        check_empty_port(&players, &mut empty_reported, got_event.port, pos);
 
-                order_idx += 1;
-            }
-            EventType::PostFrame => {
-                post = PostFrame::new(stream.slice(..size), version);
-                let mut exp_event = &event_order[order_idx];
-                let got_event = Expected {
-                    port: Port::from_repr(post.port).unwrap(),
-                    nana: post.nana,
-                    kind: EventType::PostFrame,
-                };
+                    if got_event.nana && !from_empty {
+                        nana_respawned(
+                            event_order,
+                            &mut order_idx,
+                            &players,
+                            &mut nana_present,
+                            &got_event,
+                        );
+                    }
+                    let not_exp = !from_empty
+                        && !match_expected(event_order, &mut order_idx, &got_event, &nana_dying);
 
-                let mut not_exp = *exp_event != got_event;
+                    if check_order && !need_sync && not_exp {
+                        need_sync = true;
+                        let expected = match event_order[order_idx].kind {
+                            EventType::Item => "EventType::Item or EventType::PostFrame".to_owned(),
+                            x => format!("{:?}", x),
+                        };
+                        error!("[File pos: {}] Unexpected event ordering. Expected {} for frame {}, got EventType::PostFrame for frame {}", pos, expected, fstart.frame_idx, pre.frame_index)
+                    }
+                    if post.nana {
+                        nana_dying[post.port as usize] = is_death_state(post.action_state);
+                    }
 
-                if not_exp && exp_event.nana && event_order[order_idx + 1] == got_event {
-                    order_idx += 1;
-                    not_exp = false;
+                    if has_frame_start && post.frame_index != fstart.frame_idx {
+                        error!(
+                            "[File pos: {}] PostFrame for frame {} inside frame {}",
+                            pos, post.frame_index, fstart.frame_idx
+                        );
+                    }
+
+                    post_frames.push(post.clone());
+                    if !from_empty {
+                        order_idx += 1;
+                    }
                 }
+                EventType::FrameEnd => {
+                    fend = FrameEnd::new(stream.slice(..size), end_version)
+                        .map_err(|e| at_offset(pos, e))?;
+                    fend.offset = pos;
+                    // rollbacks rewind FrameStart and FrameEnd together, so the two should always agree
+                    if fend.frame_idx != fstart.frame_idx {
+                        warn!(
+                            "[File pos: {}] FrameEnd for frame {} closes frame {}",
+                            pos, fend.frame_idx, fstart.frame_idx
+                        );
+                    }
+                    let got_event = Expected {
+                        port: Port::P1,
+                        nana: false,
+                        kind: EventType::FrameEnd,
+                    };
+
+                    let not_exp =
+                        !match_expected(event_order, &mut order_idx, &got_event, &nana_dying);
+
+                    if check_order && !need_sync && not_exp {
+                        need_sync = true;
+                        error!("[File pos: {}] Unexpected event ordering. Expected {:?} for frame {}, got EventType::FrameEnd for frame {}", pos, event_order[order_idx], fstart.frame_idx, pre.frame_index)
+                    }
+
+                    // unlike the ordering check, this catches missing events wherever they are in the frame
+                    for port in players
+                        .iter()
+                        .filter(|p| p.player_type.has_frames())
+                        .map(|p| p.port)
+                    {
+                        let i = port as usize;
+                        if !seen_pre[i][0] && !seen_post[i][0] {
+                            warn!(
+                                "[File pos: {}] No PreFrame or PostFrame for {} on frame {}",
+                                pos, port, fend.frame_idx
+                            );
+                        }
+                        for (nana, who) in [(0, ""), (1, " (Nana)")] {
+                            match (seen_pre[i][nana], seen_post[i][nana]) {
+                                (true, false) => warn!(
+                                    "[File pos: {}] PreFrame without a PostFrame for {}{} on frame {}",
+                                    pos, port, who, fend.frame_idx
+                                ),
+                                (false, true) => warn!(
+                                    "[File pos: {}] PostFrame without a PreFrame for {}{} on frame {}",
+                                    pos, port, who, fend.frame_idx
+                                ),
+                                _ => (),
+                            }
+                        }
+                    }
+
+                    drop_departed_nanas(
+                        event_order,
+                        &players,
+                        &mut nana_present,
+                        &mut nana_dying,
+                        std::array::from_fn(|i| seen_pre[i][1] || seen_post[i][1]),
+                        pos,
+                        fend.frame_idx,
+                    );
 
-                if !need_sync && not_exp && players.len() == 2 {
-                    need_sync = true;
-                    let expected = match exp_event.kind {
-                        EventType::Item => "EventType::Item or EventType::PostFrame".to_owned(),
-                        x => format!("{:?}", x),
+                    frame_ends.push(fend);
+                    order_idx = 0;
+                }
+                // Item frames don't increment counter as there can be 0 or up to 15
+                EventType::Item => {
+                    item = ItemFrame::new(stream.slice(..size), item_version)
+                        .map_err(|e| at_offset(pos, e))?;
+                    item.offset = pos;
+                    frame_items += 1;
+                    if frame_items == MAX_ITEMS_PER_FRAME + 1 {
+                        warn!(
+                            "[File pos: {}] More than {} item events in frame {}",
+                            pos, MAX_ITEMS_PER_FRAME, fstart.frame_idx
+                        );
+                    }
+                    let got_event = Expected {
+                        port: Port::P1,
+                        nana: false,
+                        kind: EventType::Item,
                     };
-                    error!("[File pos: {}] Unexpected event ordering. Expected {} for frame {}, got EventType::PostFrame for frame {}", pos, expected, fstart.frame_idx, pre.frame_index)
+
+                    let not_exp =
+                        !match_expected(event_order, &mut order_idx, &got_event, &nana_dying);
+
+                    if check_order && !need_sync && not_exp {
+                        need_sync = true;
+                        error!("[File pos: {}] Unexpected event ordering. Expected {:?} for frame {}, got EventType::Item for frame {}", pos, event_order[order_idx], fstart.frame_idx, pre.frame_index)
+                    }
+                    item_frames.push(item.clone());
                 }
-                // handling for item frames as they aren't guaranteed to exist
+                EventType::GameEnd => {
+                    if game_end.is_some() {
+                        warn!("[File pos: {}] Duplicate game end event", pos);
+                    }
+                    game_end = Some(
+                        GameEnd::new(stream.slice(..size), version)
+                            .map_err(|e| at_offset(pos, e))?,
+                    )
+                }
+                // The gecko list is too large for a single event, so it's sent in fixed-size chunks. Each
+                // chunk declares how much of its buffer is actually used.
+                EventType::MessageSplitter => {
+                    ensure!(
+                        size >= MESSAGE_SPLITTER_SIZE,
+                        ParseError::CorruptStream {
+                            offset: pos,
+                            context: format!("MessageSplitter event too small: {size} bytes"),
+                        }
+                    );
+                    let mut chunk = stream.slice(MESSAGE_SPLITTER_DATA..);
+                    let used = chunk.get_u16() as usize;
+                    let internal = chunk.get_u8();
+                    let last = chunk.get_u8() != 0;
+
+                    if used > MESSAGE_SPLITTER_DATA {
+                        error!(
+                            "[File pos: {}] MessageSplitter claims {} bytes of a {} byte buffer",
+                            pos, used, MESSAGE_SPLITTER_DATA
+                        );
+                    }
+                    if internal != EventType::GeckoList as u8 {
+                        warn!(
+                            "[File pos: {}] MessageSplitter wraps unexpected event {:#04X}",
+                            pos, internal
+                        );
+                    }
+                    gecko_len += used.min(MESSAGE_SPLITTER_DATA);
 
-                order_idx += 1;
+                    if last {
+                        match event_sizes.get(&EventType::GeckoList) {
+                            Some(declared) if *declared as usize != gecko_len => warn!(
+                                "[File pos: {}] Gecko list is {} bytes, EventPayloads declares {}",
+                                pos, gecko_len, declared
+                            ),
+                            None => warn!("[File pos: {}] Gecko list present but not declared in EventPayloads", pos),
+                            _ => (),
+                        }
+                        gecko_len = 0;
+                    }
+                }
+                _ => (),
             }
-            EventType::FrameEnd => {
-                fend = FrameEnd::new(stream.slice(..size), version);
-                let exp_event = &event_order[order_idx];
-                let got_event = Expected {
-                    port: Port::P1,
-                    nana: false,
-                    kind: EventType::FrameEnd,
-                };
+            stream.advance(size);
+            pos = file_data.len() - stream.len();
 
-                let mut not_exp = *exp_event != got_event;
+            if event == EventType::FrameEnd
+                && config
+                    .max_frames
+                    .is_some_and(|max| fend.frame_idx as i64 - FIRST_FRAME as i64 + 1 >= max as i64)
+            {
+                info!(
+                    "Stopping after {} frames",
+                    fend.frame_idx as i64 - FIRST_FRAME as i64 + 1
+                );
+                stopped_early = true;
+                break;
+            }
+            // a desynced stream can contain more events than a single frame should, don't run off the end
+            order_idx = order_idx.min(event_order.len() - 1);
+            fail_fast(config)?;
+        }
 
-                if not_exp && exp_event.nana && event_order[order_idx + 1] == got_event {
-                    order_idx += 1;
-                    not_exp = false;
-                }
+        if !in_progress && !stopped_early {
+            check_raw_length(pos, raw_length as usize, event);
+        }
+
+        info!(
+            "Expected Frames: {}, Actual Frames: {}, Rollback %: {}",
+            frame_count,
+            actual_frames,
+            (actual_frames as f32 - frame_count as f32) / actual_frames as f32
+        );
+
+        for player in players.iter().filter(|p| p.player_type.has_frames()) {
+            info!("{}: {}", player.port, player.character);
+        }
+        info!("Random seed: 0x{:08X}", game_start.random_seed);
+        if game_start.match_type != MatchType::Unknown {
+            info!("Match type: {}", game_start.match_type);
+        }
+        for player in players.iter().filter(|p| p.player_type == PlayerType::CPU) {
+            info!("{} is a level {} CPU", player.port, player.cpu_level);
+        }
+
+        let game = Game {
+            version,
+            start: game_start,
+            players,
+            end: game_end,
+            raw_length: declared_length,
+            metadata,
+            frame_starts,
+            frame_ends,
+            pre_frames,
+            post_frames,
+            item_frames,
+            raw_events,
+        };
+        game.validate(config);
+        fail_fast(config)?;
+
+        Ok(game)
+    }
+}
+
+/// Validates a full replay that has already been read into memory. When validating many replays,
+/// prefer reusing a single [`Parser`].
+pub fn validate_bytes(file_data: Bytes, config: &Config) -> Result<Game> {
+    Parser::new().validate_bytes(file_data, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::sink::RecordingSink;
+
+    fn players(p1: Character, p2: Character) -> [Player; 4] {
+        let human = |port, character| Player {
+            port,
+            player_type: PlayerType::Human,
+            character,
+            ..Default::default()
+        };
+        let empty = |port| Player {
+            port,
+            ..Default::default()
+        };
 
-                if !need_sync && not_exp && players.len() == 2 {
-                    need_sync = true;
-                    error!("[File pos: {}] Unexpected event ordering. Expected {:?} for frame {}, got EventType::FrameEnd for frame {}", pos, event_order[order_idx], fstart.frame_idx, pre.frame_index)
+        [
+            human(Port::P1, p1),
+            human(Port::P2, p2),
+            empty(Port::P3),
+            empty(Port::P4),
+        ]
+    }
+
+    fn ev(port: Port, nana: bool, kind: EventType) -> Expected {
+        Expected { port, nana, kind }
+    }
+
+    /// The event ordering bookkeeping from [`Parser::validate_bytes`], without the event parsing
+    struct Order {
+        event_order: Vec<Expected>,
+        order_idx: usize,
+        nana_present: [bool; 4],
+        nana_dying: [bool; 4],
+        seen_nana: [bool; 4],
+    }
+
+    impl Order {
+        fn new(players: &[Player; 4]) -> Self {
+            let mut event_order = Vec::new();
+            let nana_present = [true; 4];
+            build_event_order(&mut event_order, players, &nana_present);
+
+            Self {
+                event_order,
+                order_idx: 0,
+                nana_present,
+                nana_dying: [false; 4],
+                seen_nana: [false; 4],
+            }
+        }
+
+        /// False wherever the parse loop would report a desync and set `need_sync`
+        fn feed(&mut self, players: &[Player; 4], got: &Expected) -> bool {
+            let matched = match got.kind {
+                EventType::FrameStart => {
+                    let exp = &self.event_order[self.order_idx];
+                    let matched = exp.kind == EventType::FrameStart || exp.nana;
+                    self.order_idx = 1;
+                    self.seen_nana = [false; 4];
+                    matched
                 }
-                order_idx = 0;
+                EventType::PreFrame | EventType::PostFrame => {
+                    if got.nana {
+                        self.seen_nana[got.port as usize] = true;
+                        nana_respawned(
+                            &mut self.event_order,
+                            &mut self.order_idx,
+                            players,
+                            &mut self.nana_present,
+                            got,
+                        );
+                    }
+                    let matched = match_expected(
+                        &self.event_order,
+                        &mut self.order_idx,
+                        got,
+                        &self.nana_dying,
+                    );
+                    self.order_idx += 1;
+                    matched
+                }
+                EventType::FrameEnd => {
+                    let matched = match_expected(
+                        &self.event_order,
+                        &mut self.order_idx,
+                        got,
+                        &self.nana_dying,
+                    );
+                    drop_departed_nanas(
+                        &mut self.event_order,
+                        players,
+                        &mut self.nana_present,
+                        &mut self.nana_dying,
+                        self.seen_nana,
+                        0,
+                        0,
+                    );
+                    self.order_idx = 0;
+                    matched
+                }
+                _ => match_expected(
+                    &self.event_order,
+                    &mut self.order_idx,
+                    got,
+                    &self.nana_dying,
+                ),
+            };
+            self.order_idx = self.order_idx.min(self.event_order.len() - 1);
+
+            matched
+        }
+
+        fn frame(&mut self, players: &[Player; 4], events: &[Expected]) {
+            for event in events {
+                assert!(self.feed(players, event), "desync at {event:?}");
             }
-            // Item frames don't increment counter as there can be 0 or up to 15
-            EventType::Item => {
-                item = ItemFrame::new(stream.slice(..size), version);
-                let exp_event = &event_order[order_idx];
-                let got_event = Expected {
-                    port: Port::P1,
-                    nana: false,
-                    kind: EventType::Item,
-                };
+        }
+    }
 
-                let mut not_exp = *exp_event != got_event;
+    /// A frame's events, with Nana's right after each of Popo's for the ports in `nanas`
+    fn frame_events(ports: &[Port], nanas: &[Port], items: usize) -> Vec<Expected> {
+        let player_events = |kind: EventType| {
+            ports.iter().flat_map(move |&port| {
+                let nana = nanas.contains(&port).then(|| ev(port, true, kind));
+                std::iter::once(ev(port, false, kind)).chain(nana)
+            })
+        };
 
-                if not_exp && exp_event.nana && event_order[order_idx + 1] == got_event {
-                    order_idx += 1;
-                    not_exp = false;
-                }
+        let mut events = vec![ev(Port::P1, false, EventType::FrameStart)];
+        events.extend(player_events(EventType::PreFrame));
+        events.extend(std::iter::repeat_n(
+            ev(Port::P1, false, EventType::Item),
+            items,
+        ));
+        events.extend(player_events(EventType::PostFrame));
+        events.push(ev(Port::P1, false, EventType::FrameEnd));
 
-                if !need_sync && not_exp && players.len() == 2 {
-                    need_sync = true;
-                    error!("[File pos: {}] Unexpected event ordering. Expected {:?} for frame {}, got EventType::Item for frame {}", pos, event_order[order_idx], fstart.frame_idx, pre.frame_index)
-                }
+        events
+    }
+
+    #[test]
+    fn fox_vs_ice_climbers() {
+        let players = players(Character::Fox, Character::IceClimbers);
+        let mut order = Order::new(&players);
+
+        for items in [0, 1, 3] {
+            order.frame(
+                &players,
+                &frame_events(&[Port::P1, Port::P2], &[Port::P2], items),
+            );
+        }
+    }
+
+    #[test]
+    fn ice_climbers_vs_ice_climbers() {
+        let players = players(Character::IceClimbers, Character::IceClimbers);
+        let mut order = Order::new(&players);
+        let ports = [Port::P1, Port::P2];
+
+        for items in [0, 2] {
+            order.frame(&players, &frame_events(&ports, &ports, items));
+        }
+    }
+
+    #[test]
+    fn ice_climbers_after_nana_dies() {
+        let players = players(Character::Fox, Character::IceClimbers);
+        let mut order = Order::new(&players);
+        let ports = [Port::P1, Port::P2];
+
+        order.frame(&players, &frame_events(&ports, &[Port::P2], 0));
+        // her PostFrame on this frame is in a death state
+        order.nana_dying[Port::P2 as usize] = true;
+        for _ in 0..3 {
+            order.frame(&players, &frame_events(&ports, &[], 1));
+        }
+    }
+
+    #[test]
+    fn nana_respawns_after_dying() {
+        let sink = Arc::new(RecordingSink::default());
+        let _guard = SinkGuard::set(sink.clone());
+        let players = players(Character::Fox, Character::IceClimbers);
+        let mut order = Order::new(&players);
+        let ports = [Port::P1, Port::P2];
+        let p2 = Port::P2 as usize;
+
+        order.frame(&players, &frame_events(&ports, &[Port::P2], 0));
+        // her PostFrame on this frame is in a death state
+        order.nana_dying[p2] = true;
+        // Slippi stops sending her events until she respawns with Popo
+        for _ in 0..3 {
+            order.frame(&players, &frame_events(&ports, &[], 1));
+            assert!(!order.nana_present[p2]);
+            assert!(!order.event_order.iter().any(|e| e.nana));
+        }
+        for _ in 0..2 {
+            order.frame(&players, &frame_events(&ports, &[Port::P2], 1));
+            assert!(order.nana_present[p2]);
+        }
+
+        assert_eq!(sink.errors(), Vec::<String>::new());
+        assert_eq!(sink.warnings(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn nana_leaving_without_dying_is_reported() {
+        let sink = Arc::new(RecordingSink::default());
+        let _guard = SinkGuard::set(sink.clone());
+        let players = players(Character::Fox, Character::IceClimbers);
+        let mut order = Order::new(&players);
+        let ports = [Port::P1, Port::P2];
+
+        order.frame(&players, &frame_events(&ports, &[Port::P2], 0));
+        // without the death state her slots aren't optional, so the first frame without her desyncs
+        let matched: Vec<bool> = frame_events(&ports, &[], 0)
+            .iter()
+            .map(|e| order.feed(&players, e))
+            .collect();
+        assert!(matched.contains(&false));
+
+        assert_eq!(sink.warnings().len(), 1);
+    }
+
+    const NON_BROKEN: &[u8] = include_bytes!("../test_replays/non_broken.slp");
+
+    /// xorshift64, deterministic so failures can be reproduced
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    #[test]
+    fn truncated_replays_are_errors() {
+        let config = Config::default();
+        // cutting off only the top level object's closing brace is tolerated
+        let lengths = (0..4096).chain((4096..NON_BROKEN.len() - 1).step_by(997));
+
+        for n in lengths {
+            let result = validate_bytes(Bytes::from_static(&NON_BROKEN[..n]), &config);
+            assert!(result.is_err(), "truncated to {n} bytes");
+        }
+    }
+
+    #[test]
+    fn truncated_in_progress_replays_dont_panic() {
+        let config = Config::default();
+        // a raw length of 0 marks a replay that was never finalized, events are read up to the end of the file
+        let mut data = NON_BROKEN.to_vec();
+        data[SLP_HEADER.len()..SLP_HEADER.len() + 4].fill(0);
+        let data = Bytes::from(data);
+        let lengths = (0..4096).chain((4096..data.len()).step_by(data.len() / 64));
+
+        for n in lengths {
+            let _ = validate_bytes(data.slice(..n), &config);
+        }
+    }
+
+    #[test]
+    fn random_bytes_are_errors() {
+        let config = Config::default();
+        let mut state = 0x5EED_u64;
+
+        for i in 0..512 {
+            let len = next_random(&mut state) as usize % 4096;
+            // half of them get far enough to read a raw length
+            let mut data = if i % 2 == 0 {
+                SLP_HEADER.to_vec()
+            } else {
+                Vec::new()
+            };
+            data.extend((0..len).map(|_| next_random(&mut state) as u8));
+
+            let result = validate_bytes(Bytes::from(data), &config);
+            assert!(result.is_err(), "random input {i}");
+        }
+    }
+
+    #[test]
+    fn corrupted_replays_dont_panic() {
+        let config = Config {
+            max_frames: Some(600),
+            ..Config::default()
+        };
+        let mut state = 0xC0FFEE_u64;
+
+        for _ in 0..64 {
+            let mut data = NON_BROKEN.to_vec();
+            for _ in 0..8 {
+                // somewhere in the header, event payload sizes, GameStart, or the first few hundred frames
+                let pos = next_random(&mut state) as usize % 100_000;
+                data[pos] = next_random(&mut state) as u8;
             }
-            EventType::GameEnd => {
-                if game_end.is_some() {
-                    warn!("[File pos: {}] Duplicate game end event", pos);
-                }
-                game_end = Some(GameEnd::new(stream.slice(..size), version))
+
+            let _ = validate_bytes(Bytes::from(data), &config);
+        }
+    }
+
+    #[test]
+    fn out_of_range_frame_indices_are_errors() {
+        let game = validate_bytes(Bytes::from_static(NON_BROKEN), &Config::default()).unwrap();
+        let offsets = [
+            game.frame_starts[200].offset,
+            game.pre_frames[200].offset,
+            game.post_frames[200].offset,
+        ];
+
+        for offset in offsets {
+            // the frame index is the first field of all three events
+            let mut data = NON_BROKEN.to_vec();
+            data[offset + 1..offset + 5].copy_from_slice(&i32::MAX.to_be_bytes());
+            let sink = Arc::new(RecordingSink::default());
+            let config = Config {
+                sink: sink.clone(),
+                ..Config::default()
+            };
+
+            // trusting the index would size the per-port timelines at 2^31 frames
+            let result = validate_bytes(Bytes::from(data), &config);
+            assert!(!sink.errors().is_empty(), "event at {offset}");
+            let Ok(game) = result else {
+                continue;
+            };
+            for port in game.active_ports() {
+                assert!(game.post_frames_indexed(port).len() <= game.post_frames.len());
+                assert!(game.pre_frames_indexed(port).len() <= game.pre_frames.len());
             }
-            _ => (),
+            assert!(game.rng_calls().len() <= game.frame_starts.len());
         }
-        stream.advance(event_sizes[&event] as usize);
-        pos = file_data.len() - stream.len();
     }
 
-    info!(
-        "Expected Frames: {}, Actual Frames: {}, Rollback %: {}",
-        frame_count,
-        actual_frames,
-        (actual_frames as f32 - frame_count as f32) / actual_frames as f32
-    );
-    Ok(())
+    #[test]
+    fn spec_versions_cover_every_event_size_change() {
+        let sizes = |v| {
+            [
+                FrameStart::expected_size(v),
+                PreFrame::expected_size(v),
+                PostFrame::expected_size(v),
+                ItemFrame::expected_size(v),
+                FrameEnd::expected_size(v),
+                GameStart::expected_size(v),
+            ]
+        };
+        // probe every version up to the newest listed one. fitting_version can only fall back to a listed
+        // version, so each size change has to happen at one of them
+        let newest = *SPEC_VERSIONS.last().unwrap();
+        let probes: Vec<_> = (0..=newest.major)
+            .flat_map(|major| (0..=u8::MAX).map(move |minor| Version::new(major, minor, 0)))
+            .filter(|v| *v <= newest)
+            .collect();
+        for pair in probes.windows(2) {
+            if sizes(pair[0]) != sizes(pair[1]) {
+                assert!(
+                    SPEC_VERSIONS.contains(&pair[1]),
+                    "event sizes change at {}, which isn't in SPEC_VERSIONS",
+                    pair[1]
+                );
+            }
+        }
+    }
 }
@@ -1,10 +1,11 @@
 use ssbm_utils::prelude::*;
 use strum::FromRepr;
 
-use crate::{game_start::UCFToggles, utils::Tournament};
+use crate::{game_start::UCFToggles, sink::warn, utils::Tournament};
 
 #[derive(Debug, Clone, Copy, PartialEq, FromRepr, Default)]
 #[repr(u8)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub enum PlayerType {
     Human = 0,
     CPU = 1,
@@ -13,8 +14,18 @@ pub enum PlayerType {
     Empty = 3,
 }
 
+impl PlayerType {
+    /// True if a player of this type produces PreFrame/PostFrame events. Demo players (e.g. attract mode)
+    /// are included, they are controlled by the game but are simulated like any other player.
+    #[inline]
+    pub fn has_frames(self) -> bool {
+        !matches!(self, PlayerType::Empty)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, FromRepr, Default)]
 #[repr(u8)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub enum TeamShade {
     #[default]
     Normal,
@@ -24,6 +35,7 @@ pub enum TeamShade {
 
 #[derive(Debug, Clone, Copy, PartialEq, FromRepr, Default)]
 #[repr(u8)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub enum TeamID {
     #[default]
     Red,
@@ -32,12 +44,15 @@ pub enum TeamID {
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct Player {
     pub port: Port,
     pub player_type: PlayerType,
     pub character: Character,
     pub starting_stocks: u8,
     pub costume: Costume,
+    /// The raw costume index from the character select screen
+    pub costume_idx: u8,
     pub team_shade: TeamShade,
     pub handicap: u8,
     pub team_id: TeamID,
@@ -53,6 +68,105 @@ pub struct Player {
     pub display_name: Option<String>,
 }
 
+/// Highest handicap the game's menus allow
+pub const MAX_HANDICAP: u8 = 9;
+/// Highest percent the game can display, and so the highest starting or respawn damage that can be set
+pub const MAX_PERCENT: u16 = 999;
+
+/// Longest display name Slippi allows, in characters
+pub const MAX_DISPLAY_NAME_LEN: usize = 15;
+
+impl Player {
+    /// Human readable name of the player's costume, e.g. "Green" for Fox's 4th costume
+    pub fn costume_name(&self) -> Option<&'static str> {
+        costume_name(self.character, self.costume_idx)
+    }
+
+    /// The player's costume as worn by the given form of their character. Zelda and Sheik, and Popo and Nana,
+    /// share a single costume index from the character select screen, but each form has its own colors for
+    /// it. Use [`crate::game::Game::form_at`] for the form that's active on a given frame.
+    pub fn costume_for(&self, form: Character) -> Costume {
+        form.get_costume(self.costume_idx)
+    }
+
+    /// Human readable name of [`Player::costume_for`], e.g. "Yellow" for Nana when Popo is in green
+    pub fn costume_name_for(&self, form: Character) -> Option<&'static str> {
+        costume_name(form, self.costume_idx)
+    }
+
+    /// Flags names that the official client couldn't have produced: control characters, display names over
+    /// [`MAX_DISPLAY_NAME_LEN`], and connect codes not in `ABCD#123` form. Usually a sign of a modified client.
+    pub fn validate_names(&self) {
+        let port = self.port;
+
+        if let Some(name) = self.display_name.as_deref() {
+            if name.chars().any(char::is_control) {
+                warn!("Display name for {port} contains control characters: {name:?}");
+            }
+            if name.chars().count() > MAX_DISPLAY_NAME_LEN {
+                warn!("Display name for {port} is longer than {MAX_DISPLAY_NAME_LEN} characters: {name:?}");
+            }
+        }
+
+        if let Some(code) = self.connect_code.as_deref().filter(|c| !c.is_empty()) {
+            if !is_valid_connect_code(code) {
+                warn!("Malformed connect code for {port}: {code:?}");
+            }
+        }
+    }
+}
+
+/// 1-4 uppercase letters or digits, `#`, then 1-3 digits
+fn is_valid_connect_code(code: &str) -> bool {
+    let Some((tag, number)) = code.split_once('#') else {
+        return false;
+    };
+
+    (1..=4).contains(&tag.len())
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        && (1..=3).contains(&number.len())
+        && number.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Costume names per character, in character select screen order
+pub fn costume_name(character: Character, costume_idx: u8) -> Option<&'static str> {
+    let names: &[&'static str] = match character {
+        Character::CaptainFalcon => &["Default", "Black", "Red", "White", "Green", "Blue"],
+        Character::DonkeyKong => &["Default", "Black", "Red", "Blue", "Green"],
+        Character::Fox => &["Default", "Red", "Blue", "Green"],
+        Character::GameAndWatch => &["Default", "Red", "Blue", "Green"],
+        Character::Kirby => &["Default", "Yellow", "Blue", "Red", "Green", "White"],
+        Character::Bowser => &["Default", "Red", "Blue", "Black"],
+        Character::Link => &["Default", "Red", "Blue", "Black", "White"],
+        Character::Luigi => &["Default", "White", "Blue", "Red"],
+        Character::Mario => &["Default", "Yellow", "Black", "Blue", "Green"],
+        Character::Marth => &["Default", "Red", "Green", "Black", "White"],
+        Character::Mewtwo => &["Default", "Red", "Blue", "Green"],
+        Character::Ness => &["Default", "Yellow", "Blue", "Green"],
+        Character::Peach => &["Default", "Daisy", "White", "Blue", "Green"],
+        Character::Pikachu => &["Default", "Red", "Party Hat", "Cowboy Hat"],
+        Character::IceClimbers | Character::Popo => &["Default", "Green", "Orange", "Red"],
+        // Nana's colors for each of Popo's costumes
+        Character::Nana => &["Default", "Yellow", "Aqua", "White"],
+        Character::Jigglypuff => &["Default", "Red", "Blue", "Headband", "Crown"],
+        Character::Samus => &["Default", "Pink", "Black", "Green", "Purple"],
+        Character::Yoshi => &["Default", "Red", "Blue", "Yellow", "Pink", "Cyan"],
+        Character::Zelda => &["Default", "Red", "Blue", "Green", "White"],
+        Character::Sheik => &["Default", "Red", "Blue", "Green", "White"],
+        Character::Falco => &["Default", "Red", "Blue", "Green"],
+        Character::YoungLink => &["Default", "Red", "Blue", "White", "Black"],
+        Character::DrMario => &["Default", "Red", "Blue", "Green", "Black"],
+        Character::Roy => &["Default", "Red", "Blue", "Green", "Yellow"],
+        Character::Pichu => &["Default", "Red", "Blue", "Green"],
+        Character::Ganondorf => &["Default", "Red", "Blue", "Green", "Purple"],
+        _ => &["Default"],
+    };
+
+    names.get(costume_idx as usize).copied()
+}
+
 impl Tournament for Player {
     fn is_legal(&self) -> bool {
         if self.player_type == PlayerType::Empty {
@@ -72,5 +186,60 @@ impl Tournament for Player {
             && self.bitfield >> 1 == 0
             && self.damage_spawn == 0
             && self.damage_start == 0
+            && self.model_scale == 1.0
+            && self.offense_ratio == 1.0
+            && self.defense_ratio == 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::sink::{RecordingSink, SinkGuard};
+
+    #[test]
+    fn connect_codes() {
+        for code in ["ABCD#123", "A#1", "FOX2#99", "X1#0"] {
+            assert!(is_valid_connect_code(code), "{code}");
+        }
+        for code in [
+            "",
+            "ABCD",
+            "ABCD#",
+            "#123",
+            "ABCDE#123",
+            "ABCD#1234",
+            "abcd#123",
+            "AB D#123",
+            "ABCD#12A",
+            "AB#1#2",
+            "ＡＢＣ#123",
+        ] {
+            assert!(!is_valid_connect_code(code), "{code}");
+        }
+    }
+
+    #[test]
+    fn validate_names_flags_spoofed_names() {
+        let sink = Arc::new(RecordingSink::default());
+        let _guard = SinkGuard::set(sink.clone());
+
+        let player = |name: &str, code: &str| Player {
+            display_name: Some(name.to_string()),
+            connect_code: Some(code.to_string()),
+            ..Default::default()
+        };
+
+        player("Mang0", "MANG#0").validate_names();
+        // netplay codes are empty offline
+        player("Mang0", "").validate_names();
+        assert_eq!(sink.warnings(), Vec::<String>::new());
+
+        player("Man\u{7}g0", "MANG#0").validate_names();
+        player("ThisNameIsFarTooLong", "MANG#0").validate_names();
+        player("Mang0", "mang#0").validate_names();
+        assert_eq!(sink.warnings().len(), 3);
     }
 }
@@ -1,13 +1,43 @@
-use crate::utils::Version;
+use crate::{
+    config::Config,
+    sink::warn,
+    utils::{ensure_len, is_valid_orientation, max_jumps, ParseError, Version},
+};
+use anyhow::{ensure, Result};
 use bytes::{Buf, Bytes};
-use log::warn;
 use ssbm_utils::prelude::*;
 use ssbm_utils::types::*;
 use ssbm_utils::{pos, vel};
 
+/// Bits of `PostFrame::flags`. Flag byte 1 occupies the lowest 8 bits, flag byte 5 the highest.
+pub mod flags {
+    pub const REFLECT: u64 = 1 << 4;
+    pub const UNTOUCHABLE: u64 = 1 << 10;
+    pub const FAST_FALL: u64 = 1 << 11;
+    pub const HITLAG: u64 = 1 << 13;
+    pub const SHIELD: u64 = 1 << 23;
+    pub const HITSTUN: u64 = 1 << 25;
+    pub const SHIELD_TOUCH: u64 = 1 << 26;
+    pub const POWERSHIELD: u64 = 1 << 29;
+    pub const FOLLOWER: u64 = 1 << 35;
+    pub const SLEEP: u64 = 1 << 36;
+    pub const DEAD: u64 = 1 << 38;
+    pub const OFFSCREEN: u64 = 1 << 39;
+}
+
+/// Values of `PostFrame::hurtbox_state`
+pub mod hurtbox {
+    pub const VULNERABLE: u8 = 0;
+    pub const INVULNERABLE: u8 = 1;
+    pub const INTANGIBLE: u8 = 2;
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct PostFrame {
     pub frame_index: i32,
+    /// Position of the command byte in the file, see [`crate::frame::FrameStart::offset`]
+    pub offset: usize,
     pub port: u8,
     pub nana: bool,
     pub character: u8,
@@ -38,10 +68,33 @@ pub struct PostFrame {
 }
 
 impl PostFrame {
-    pub fn new(mut stream: Bytes, version: Version) -> Self {
+    /// Size of the event payload for the given replay version
+    pub fn expected_size(version: Version) -> usize {
+        if version.at_least(3, 16, 0) {
+            84
+        } else if version.at_least(3, 11, 0) {
+            80
+        } else if version.at_least(3, 8, 0) {
+            76
+        } else if version.at_least(3, 5, 0) {
+            72
+        } else if version.at_least(3, 1, 0) {
+            52
+        } else if version.at_least(2, 0, 0) {
+            51
+        } else if version.at_least(0, 2, 0) {
+            37
+        } else {
+            33
+        }
+    }
+
+    pub fn new(mut stream: Bytes, version: Version, config: &Config) -> Result<Self> {
+        ensure_len(&stream, Self::expected_size(version), "PostFrame")?;
         let mut y_speed = 0.0;
         let result = Self {
             frame_index: stream.get_i32(),
+            offset: 0,
             port: stream.get_u8(),
             nana: stream.get_u8() != 0,
             character: stream.get_u8(),
@@ -85,15 +138,46 @@ impl PostFrame {
             instance_id: version.at_least(3, 16, 0).then(|| stream.get_u16()),
         };
 
-        result.validate();
+        ensure!(
+            result.port < 4,
+            ParseError::Value("port 0-3".to_string(), result.port.to_string())
+        );
+
+        result.validate(config);
+
+        Ok(result)
+    }
+
+    /// True if the given bit(s) from [`flags`] are set. Always false before v2.0.0
+    #[inline]
+    pub fn has_flag(&self, flag: u64) -> bool {
+        self.flags.is_some_and(|f| f & flag != 0)
+    }
 
-        result
+    /// The last move this character landed, if any
+    #[inline]
+    pub fn last_attack(&self) -> Option<Attack> {
+        Attack::from_repr(self.last_attack_landed)
     }
 
-    pub fn validate(&self) {
+    /// The opponent credited with this character's damage (and death), from `last_hit_by`. `None` if nobody has
+    /// hit them, or if the credit goes to their own port.
+    #[inline]
+    pub fn attacker(&self) -> Option<Port> {
+        // last_hit_by is the attacker's port, anything past P4 means nobody
+        Port::from_repr(self.last_hit_by).filter(|p| *p as u8 != self.port)
+    }
+
+    pub fn validate(&self, config: &Config) {
         let idx = self.frame_index;
         let port = self.port;
-        let character = Character::try_from_internal(self.character).unwrap();
+        let Ok(character) = Character::try_from_internal(self.character) else {
+            warn!(
+                "[Frame {idx}, Port{port}] Invalid character: {}",
+                self.character
+            );
+            return;
+        };
         if self.nana && character != Character::Nana {
             warn!(
                 "[Frame {idx}, Port{port}] Nana frame for non-nana character: {}",
@@ -106,7 +190,7 @@ impl PostFrame {
         ) {
             warn!("[Frame {idx}, Port{port}] Unknown state ID '{x}' for character {character}",);
         }
-        if !matches!(self.orientation, -1.0 | 0.0 | 1.0) {
+        if !is_valid_orientation(self.orientation, config.orientation_epsilon) {
             warn!(
                 "[Frame {idx}, Port{port}] Invalid orientation raw value: {}",
                 self.orientation
@@ -124,7 +208,7 @@ impl PostFrame {
                 self.shield_health,
             )
         }
-        if Attack::from_repr(self.last_attack_landed).is_none() {
+        if self.last_attack().is_none() {
             warn!(
                 "[Frame {idx}, Port{port}] Invalid attack ID: {}",
                 self.last_attack_landed
@@ -135,6 +219,14 @@ impl PostFrame {
                 "[Frame {idx}, Port{port}] Invalid flag bits set: {:040b}",
                 self.flags.unwrap());
         }
+        if self
+            .jumps_remaining
+            .is_some_and(|j| j > max_jumps(character))
+        {
+            warn!(
+                "[Frame {idx}, Port{port}] Invalid jumps remaining for {character}: {}",
+                self.jumps_remaining.unwrap());
+        }
         if self.l_cancel.is_some_and(|l| l > 2) {
             warn!(
                 "[Frame {idx}, Port{port}] Invalid l cancel value: {}",
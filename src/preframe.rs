@@ -1,13 +1,22 @@
-use crate::{player::Player, utils::Version};
+use crate::{
+    config::Config,
+    inputs::invalid_engine_buttons,
+    player::Player,
+    sink::{error, warn},
+    utils::{ensure_len, is_valid_orientation, ParseError, Version},
+};
+use anyhow::{ensure, Result};
 use bytes::{Buf, Bytes};
-use log::{error, warn};
 use ssbm_utils::prelude::*;
 use ssbm_utils::types::*;
 use ssbm_utils::{pos, stick_pos};
 
 #[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct PreFrame {
     pub frame_index: i32,
+    /// Position of the command byte in the file, see [`crate::frame::FrameStart::offset`]
+    pub offset: usize,
     pub port: u8,
     pub nana: bool,
     pub random_seed: u32,
@@ -27,16 +36,25 @@ pub struct PreFrame {
 }
 
 impl PreFrame {
-    pub fn validate(&self) {
+    /// `character` is the port's character from GameStart
+    pub fn validate(&self, config: &Config, version: Version, character: Character) {
         let port = self.port;
         let idx = self.frame_index;
+        // PreFrames don't carry the character, so this can't be checked against Character::Nana the way
+        // PostFrame::validate does. Only the Ice Climbers have a follower.
+        if self.nana && character != Character::IceClimbers {
+            error!(
+                "[Frame {idx}, Port {}] Nana frame but is playing {}",
+                Port::from_repr(port).unwrap(), character
+            );
+        }
         if matches!(self.action_state, State::Unknown(_)) {
             warn!(
                 "[Frame {idx}, Port {}] Unknown state: {}",
                 Port::from_repr(port).unwrap(), self.action_state
             );
         }
-        if !matches!(self.orientation, -1.0 | 0.0 | 1.0) {
+        if !is_valid_orientation(self.orientation, config.orientation_epsilon) {
             warn!(
                 "[Frame {idx}, Port {}] Invalid orientation raw value: {}",
                 Port::from_repr(port).unwrap(), self.orientation
@@ -60,7 +78,7 @@ impl PreFrame {
                 Port::from_repr(port).unwrap(), self.engine_trigger
             );
         }
-        if self.engine_buttons & 0x7F00_E080 != 0 {
+        if self.engine_buttons & invalid_engine_buttons(version) != 0 {
             warn!(
                 "[Frame {idx}, Port {}] Invalid bits set in engine buttons: {:032b}",
                 Port::from_repr(port).unwrap(), self.engine_buttons
@@ -78,48 +96,56 @@ impl PreFrame {
                 Port::from_repr(port).unwrap(), self.controller_r
             );
         }
-        // if self.raw_stick_x.is_some_and(|x| !(-110..=110).contains(&x)) {
-        //     warn!(
-        //         "[Frame {idx}, Port{port}] Unexpected raw stick x: {}. Expected stick value in range -110 through 110",
-        //         self.raw_stick_x.unwrap()
-        //     );
-        // }
         if self.percent.is_some_and(|p| !(0.0..1000.0).contains(&p)) {
             warn!(
                 "[Frame {idx}, Port {}] Invalid percent: {}",
                 Port::from_repr(port).unwrap(), self.percent.unwrap()
             );
         }
-        // if self.raw_stick_y.is_some_and(|y| !(-110..=110).contains(&y)) {
-        //     warn!(
-        //         "[Frame {idx}, Port{port}] Unexpected raw stick y: {}. Expected stick value in range -110 through 110",
-        //         self.raw_stick_y.unwrap()
-        //     );
-        // }
+        // the raw stick values are checked against the processed joystick in `Game::validate_raw_sticks`
     }
 
-    pub fn new(mut stream: Bytes, version: Version, players: &[Player; 4]) -> Self {
+    /// Size of the event payload for the given replay version
+    pub fn expected_size(version: Version) -> usize {
+        if version.at_least(3, 15, 0) {
+            64
+        } else if version.at_least(1, 4, 0) {
+            63
+        } else if version.at_least(1, 2, 0) {
+            59
+        } else {
+            58
+        }
+    }
+
+    pub fn new(
+        mut stream: Bytes,
+        version: Version,
+        players: &[Player; 4],
+        config: &Config,
+    ) -> Result<Self> {
+        ensure_len(&stream, Self::expected_size(version), "PreFrame")?;
         let frame_index = stream.get_i32();
         let port = stream.get_u8();
         let follower = stream.get_u8() == 1;
+        ensure!(
+            port < 4,
+            ParseError::Value("port 0-3".to_string(), port.to_string())
+        );
 
         let character = players[port as usize].character;
-        if character != Character::IceClimbers && follower {
-            error!(
-                "[Frame {frame_index}, Port {port}] Has Nana frame but is playing {}",
-                character
-            );
-        }
         let result = Self {
             frame_index,
+            offset: 0,
             port,
             nana: follower,
             random_seed: stream.get_u32(),
             action_state: {
-                let state = State::from_state_and_char(stream.get_u16(), Some(character));
+                let state_id = stream.get_u16();
+                let state = State::from_state_and_char(state_id, Some(character));
                 if let State::Unknown(x) = state {
                     if character == Character::Zelda {
-                        State::from_state_and_char(stream.get_u16(), Some(Character::Sheik))
+                        State::from_state_and_char(state_id, Some(Character::Sheik))
                     } else {
                         State::Unknown(x)
                     }
@@ -141,8 +167,8 @@ impl PreFrame {
             raw_stick_y: version.at_least(3, 15, 0).then(|| stream.get_i8()),
         };
 
-        result.validate();
+        result.validate(config, version, character);
 
-        result
+        Ok(result)
     }
 }
@@ -0,0 +1,98 @@
+//! Melee's random number generator, as captured by `FrameStart::random_seed`.
+//!
+//! Everything random in the game (stage transformations on Pokémon Stadium, Fountain of Dreams platform
+//! movement, item spawns, Peach turnips, ...) draws from a single linear congruential generator. The seed at the
+//! start of each frame is enough to tell how many times the RNG was used during the previous frame, which is
+//! what deterministic analysis and TAS verification need to line a replay up with a re-simulation.
+//!
+//! Working out *what* each draw decided from the seed alone would mean emulating the game logic that consumed it.
+//! Replays from v3.18.0 record the outcomes for the transforming stages directly instead, see
+//! [`Game::stadium_transformations`] and [`Game::fod_platforms`].
+
+use crate::game::{dedup, Game};
+
+/// Multiplier of Melee's LCG: `seed = seed * RNG_MULTIPLIER + RNG_INCREMENT`
+pub const RNG_MULTIPLIER: u32 = 214013;
+pub const RNG_INCREMENT: u32 = 2531011;
+
+/// Give up looking for the next frame's seed after this many steps. Even item-heavy frames use the RNG far
+/// fewer times than this.
+pub const MAX_RNG_STEPS: u32 = 10_000;
+
+/// Advances the seed by one RNG call
+#[inline]
+pub fn next_seed(seed: u32) -> u32 {
+    seed.wrapping_mul(RNG_MULTIPLIER)
+        .wrapping_add(RNG_INCREMENT)
+}
+
+/// Number of RNG calls it takes to get from `from` to `to`, `None` if it takes more than [`MAX_RNG_STEPS`]
+pub fn rng_steps(from: u32, to: u32) -> Option<u32> {
+    let mut seed = from;
+    for steps in 0..=MAX_RNG_STEPS {
+        if seed == to {
+            return Some(steps);
+        }
+        seed = next_seed(seed);
+    }
+
+    None
+}
+
+impl Game {
+    /// Number of RNG calls made during each frame, as `(frame index, calls)`, in frame order with rollbacks
+    /// resolved. A frame's calls are the steps between its seed and the next frame's, so the last frame isn't
+    /// included. `None` means the next seed couldn't be reached, which happens when the seed is re-seeded
+    /// (e.g. a stage reload) or the frames aren't consecutive.
+    pub fn rng_calls(&self) -> Vec<(i32, Option<u32>)> {
+        let frames = dedup(self.frame_starts.iter(), |f| f.frame_idx);
+
+        frames
+            .windows(2)
+            .map(|w| {
+                let calls = (w[1].frame_idx == w[0].frame_idx + 1)
+                    .then(|| rng_steps(w[0].random_seed, w[1].random_seed))
+                    .flatten();
+                (w[0].frame_idx, calls)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_seed_matches_the_lcg() {
+        assert_eq!(next_seed(0), RNG_INCREMENT);
+        assert_eq!(next_seed(1), RNG_MULTIPLIER + RNG_INCREMENT);
+        // wraps instead of overflowing
+        assert_eq!(
+            next_seed(u32::MAX),
+            RNG_INCREMENT.wrapping_sub(RNG_MULTIPLIER)
+        );
+    }
+
+    #[test]
+    fn rng_steps_counts_calls() {
+        let seed = 0x1234_5678;
+        assert_eq!(rng_steps(seed, seed), Some(0));
+
+        let mut to = seed;
+        for _ in 0..37 {
+            to = next_seed(to);
+        }
+        assert_eq!(rng_steps(seed, to), Some(37));
+    }
+
+    #[test]
+    fn rng_steps_gives_up() {
+        let seed = 0x1234_5678;
+        let mut to = seed;
+        for _ in 0..=MAX_RNG_STEPS {
+            to = next_seed(to);
+        }
+        assert_eq!(rng_steps(seed, to), None);
+    }
+}
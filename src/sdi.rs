@@ -0,0 +1,78 @@
+use ssbm_utils::{prelude::Port, types::StickPos};
+
+use crate::{
+    frame::FIRST_FRAME,
+    game::Game,
+    postframe::{flags, PostFrame},
+};
+
+/// How far the joystick has to be pushed (on the unit circle) for a new direction to count as an SDI input
+pub const SDI_THRESHOLD: f32 = 0.7;
+
+/// Inputs during one hitlag window in which the player was hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdiEvent {
+    /// First frame of hitlag, i.e. the frame the hit landed
+    pub start_frame: i32,
+    /// Last frame of hitlag
+    pub end_frame: i32,
+    /// Number of times the joystick was pushed past [`SDI_THRESHOLD`] into a new direction
+    pub inputs: u32,
+}
+
+/// The 8-way direction the stick is pushed in, `None` when it isn't pushed past [`SDI_THRESHOLD`]
+fn sdi_direction(stick: &StickPos) -> Option<u8> {
+    if stick.x.hypot(stick.y) < SDI_THRESHOLD {
+        return None;
+    }
+    let angle = stick.y.atan2(stick.x).to_degrees().rem_euclid(360.0);
+
+    Some(((angle + 22.5) / 45.0) as u8 % 8)
+}
+
+impl Game {
+    /// Every hitlag window in which the given port was hit, with the number of SDI inputs during it. A window
+    /// starts on a frame where the player's percent went up and lasts for as long as they're in hitlag.
+    /// Requires `PostFrame::flags` (v2.0.0+).
+    pub fn sdi_inputs(&self, port: Port) -> Vec<SdiEvent> {
+        let post = self.post_frames(port);
+        let pre = self.pre_frames_indexed(port);
+        // the inputs that went into a post-frame are in the pre-frame with the same index
+        let inputs = |frame: &PostFrame| {
+            let i = usize::try_from(frame.frame_index as i64 - FIRST_FRAME as i64).ok()?;
+            pre.get(i).copied().flatten()
+        };
+
+        let mut events = Vec::new();
+        let mut i = 1;
+        while i < post.len() {
+            let hit = post[i].percent > post[i - 1].percent && post[i].has_flag(flags::HITLAG);
+            if !hit {
+                i += 1;
+                continue;
+            }
+
+            let start_frame = post[i].frame_index;
+            let mut prev_dir = inputs(post[i - 1]).and_then(|f| sdi_direction(&f.joystick));
+            let mut count = 0;
+            let mut end = i;
+            while end < post.len() && post[end].has_flag(flags::HITLAG) {
+                let dir = inputs(post[end]).and_then(|f| sdi_direction(&f.joystick));
+                if dir.is_some() && dir != prev_dir {
+                    count += 1;
+                }
+                prev_dir = dir;
+                end += 1;
+            }
+
+            events.push(SdiEvent {
+                start_frame,
+                end_frame: post[end - 1].frame_index,
+                inputs: count,
+            });
+            i = end;
+        }
+
+        events
+    }
+}
@@ -0,0 +1,315 @@
+//! Where validation diagnostics go.
+//!
+//! Everything the parser reports at info level and above (malformed values, ordering problems, suspicious
+//! inputs) is sent to the [`ValidationSink`] in `Config::sink`. The default, [`LogSink`], forwards to the `log`
+//! crate, embedders without a global logger (GUIs, WASM) can collect the messages themselves instead.
+//!
+//! Trace and debug output is for developing the parser rather than for users, and always goes to `log`.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use log::Level;
+
+pub trait ValidationSink: Send + Sync {
+    /// The replay is malformed
+    fn error(&self, message: &str);
+    /// The replay is suspicious or unusual, but may be legitimate
+    fn warn(&self, message: &str);
+    fn info(&self, message: &str);
+}
+
+impl fmt::Debug for dyn ValidationSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("dyn ValidationSink")
+    }
+}
+
+/// Forwards every message to the `log` macro of the same level
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+impl ValidationSink for LogSink {
+    fn error(&self, message: &str) {
+        log::error!("{message}");
+    }
+
+    fn warn(&self, message: &str) {
+        log::warn!("{message}");
+    }
+
+    fn info(&self, message: &str) {
+        log::info!("{message}");
+    }
+}
+
+/// Counts per-frame warnings (those starting with `[Frame ...]` or `[File pos: ...]`) by category instead of
+/// forwarding them, everything else goes straight to the wrapped sink. [`SummarySink::report`] sends the counts
+/// on as a single warning. Used for `Config::summary_only`.
+#[derive(Debug)]
+pub struct SummarySink {
+    inner: Arc<dyn ValidationSink>,
+    counts: Mutex<HashMap<String, usize>>,
+}
+
+impl SummarySink {
+    pub fn new(inner: Arc<dyn ValidationSink>) -> Self {
+        Self {
+            inner,
+            counts: Mutex::default(),
+        }
+    }
+
+    /// Reports the warnings counted since the last call, most common first, and resets the counts
+    pub fn report(&self) {
+        let counts = std::mem::take(&mut *self.counts.lock().unwrap());
+        if counts.is_empty() {
+            return;
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let summary = counts
+            .iter()
+            .map(|(category, count)| format!("{count} {category}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.inner.warn(&format!("Frame warnings: {summary}"));
+    }
+}
+
+impl ValidationSink for SummarySink {
+    fn error(&self, message: &str) {
+        self.inner.error(message);
+    }
+
+    fn warn(&self, message: &str) {
+        match warning_category(message) {
+            Some(category) => *self.counts.lock().unwrap().entry(category).or_default() += 1,
+            None => self.inner.warn(message),
+        }
+    }
+
+    fn info(&self, message: &str) {
+        self.inner.info(message);
+    }
+}
+
+/// The message of a per-frame warning, minus its location prefix and anything after the first colon (usually
+/// the offending value). Digits are replaced with `#` so the same warning on different frames or ports lands
+/// in the same category. `None` for warnings that aren't tied to a frame.
+fn warning_category(message: &str) -> Option<String> {
+    if !message.starts_with("[Frame ") && !message.starts_with("[File pos: ") {
+        return None;
+    }
+    let (_, rest) = message.split_once("] ")?;
+    let text = rest.split(':').next().unwrap_or(rest);
+
+    let mut category = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !c.is_ascii_digit() {
+            category.push(c);
+        } else if !category.ends_with('#') {
+            category.push('#');
+        }
+    }
+
+    Some(category)
+}
+
+/// Keeps every error and warning, for tests
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct RecordingSink {
+    errors: Mutex<Vec<String>>,
+    warnings: Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl RecordingSink {
+    pub(crate) fn errors(&self) -> Vec<String> {
+        self.errors.lock().unwrap().clone()
+    }
+
+    pub(crate) fn warnings(&self) -> Vec<String> {
+        self.warnings.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl ValidationSink for RecordingSink {
+    fn error(&self, message: &str) {
+        self.errors.lock().unwrap().push(message.to_string());
+    }
+
+    fn warn(&self, message: &str) {
+        self.warnings.lock().unwrap().push(message.to_string());
+    }
+
+    fn info(&self, _message: &str) {}
+}
+
+struct ActiveSink {
+    sink: Arc<dyn ValidationSink>,
+    /// The first error reported since the guard was set, for `Config::fail_fast`
+    first_error: Option<String>,
+    counts: Counts,
+}
+
+/// Number of diagnostics reported at each level, see [`counts`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Counts {
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+thread_local! {
+    static SINK: RefCell<Option<ActiveSink>> = const { RefCell::new(None) };
+}
+
+/// Routes diagnostics on this thread to `sink` until dropped, then restores whatever sink was active before.
+/// Outside of a guard diagnostics go to [`LogSink`].
+pub(crate) struct SinkGuard(Option<ActiveSink>);
+
+impl SinkGuard {
+    pub(crate) fn set(sink: Arc<dyn ValidationSink>) -> Self {
+        let active = ActiveSink {
+            sink,
+            first_error: None,
+            counts: Counts::default(),
+        };
+        Self(SINK.with(|s| s.replace(Some(active))))
+    }
+}
+
+impl Drop for SinkGuard {
+    fn drop(&mut self) {
+        SINK.with(|s| {
+            let inner = s.replace(self.0.take());
+            // whatever was reported under this guard was also reported while the outer one was active
+            if let (Some(outer), Some(inner)) = (s.borrow_mut().as_mut(), inner) {
+                outer.counts.errors += inner.counts.errors;
+                outer.counts.warnings += inner.counts.warnings;
+                if outer.first_error.is_none() {
+                    outer.first_error = inner.first_error;
+                }
+            }
+        });
+    }
+}
+
+/// True once any error has been reported to the current guard's sink. Cheap enough to check after every event,
+/// unlike [`first_error`] it doesn't copy the message.
+#[inline]
+pub(crate) fn has_errors() -> bool {
+    SINK.with(|s| s.borrow().as_ref().is_some_and(|a| a.counts.errors > 0))
+}
+
+/// The first error reported to the current guard's sink, if any
+pub(crate) fn first_error() -> Option<String> {
+    SINK.with(|s| s.borrow().as_ref().and_then(|a| a.first_error.clone()))
+}
+
+/// Diagnostics reported since the current guard was set, including under guards nested inside it
+#[cfg(any(feature = "fs", feature = "metrics"))]
+pub(crate) fn counts() -> Counts {
+    SINK.with(|s| {
+        s.borrow()
+            .as_ref()
+            .map_or_else(Counts::default, |a| a.counts)
+    })
+}
+
+pub(crate) fn emit(level: Level, args: fmt::Arguments) {
+    let message = args.to_string();
+    // the sink is called outside of the borrow, in case it reports something itself
+    let sink = SINK.with(|s| {
+        let mut active = s.borrow_mut();
+        let active = active.as_mut()?;
+        match level {
+            Level::Error => {
+                active.counts.errors += 1;
+                if active.first_error.is_none() {
+                    active.first_error = Some(message.clone());
+                }
+            }
+            Level::Warn => active.counts.warnings += 1,
+            _ => (),
+        }
+        Some(active.sink.clone())
+    });
+    let sink: &dyn ValidationSink = match &sink {
+        Some(sink) => sink.as_ref(),
+        None => &LogSink,
+    };
+
+    match level {
+        Level::Error => sink.error(&message),
+        Level::Warn => sink.warn(&message),
+        _ => sink.info(&message),
+    }
+}
+
+// Drop-in replacements for the `log` macros of the same name
+
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::sink::emit(log::Level::Error, format_args!($($arg)*)) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => { $crate::sink::emit(log::Level::Warn, format_args!($($arg)*)) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::sink::emit(log::Level::Info, format_args!($($arg)*)) };
+}
+
+pub(crate) use {error, info, warn};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warning_categories() {
+        assert_eq!(
+            warning_category("[Frame 812, Port P1] Invalid orientation: 0.5").as_deref(),
+            Some("Invalid orientation")
+        );
+        // numbers are collapsed so the same warning on different frames and ports lands together
+        assert_eq!(
+            warning_category("[File pos: 1234] More than 15 item events in frame 30").as_deref(),
+            Some("More than # item events in frame #")
+        );
+        assert_eq!(warning_category("Replay has 3 human players"), None);
+        assert_eq!(warning_category("[Port P2] Buttons disagree"), None);
+    }
+
+    #[test]
+    fn summary_sink_counts_frame_warnings() {
+        let inner = Arc::new(RecordingSink::default());
+        let summary = SummarySink::new(inner.clone());
+
+        summary.warn("[Frame 1, Port P1] Invalid orientation: 0.5");
+        summary.warn("[Frame 2, Port P2] Invalid orientation: -3");
+        summary.warn("[File pos: 99] Unknown state: 1000");
+        summary.warn("Replay has 3 human players");
+        summary.error("[Frame 3, Port P1] Something broke");
+        assert_eq!(inner.warnings(), vec!["Replay has 3 human players"]);
+        assert_eq!(inner.errors().len(), 1);
+
+        summary.report();
+        assert_eq!(
+            inner.warnings().last().map(String::as_str),
+            Some("Frame warnings: 2 Invalid orientation, 1 Unknown state")
+        );
+
+        // the counts are reset after each report
+        summary.report();
+        assert_eq!(inner.warnings().len(), 2);
+    }
+}
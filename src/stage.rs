@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use bytes::Buf;
+use ssbm_utils::enums::stage::StageID;
+use strum::FromRepr;
+
+use crate::{game::Game, sink::warn, stats::is_death_state};
+
+/// Command bytes of the stage events Slippi records from v3.18.0. The parser doesn't know them as event types,
+/// they're skipped by their declared size and kept in `Game::raw_events`.
+const FOD_PLATFORM_EVENT: u8 = 0x3F;
+const STADIUM_TRANSFORMATION_EVENT: u8 = 0x41;
+
+/// Pokémon Stadium's stage forms, as recorded in its transformation events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u16)]
+pub enum StadiumTransformation {
+    Fire = 3,
+    Grass = 4,
+    Normal = 5,
+    Rock = 6,
+    Water = 9,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum FodPlatform {
+    Right = 0,
+    Left = 1,
+}
+
+/// Height of one of Fountain of Dreams' side platforms on a given frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlatformHeight {
+    pub frame_index: i32,
+    pub platform: FodPlatform,
+    pub height: f32,
+}
+
+/// How far, in units, a grounded player's y position may be from the surface they're standing on
+pub const GROUND_TOLERANCE: f32 = 0.5;
+
+/// Height of every surface a player can stand on (main stage first, then platforms). Only stages whose
+/// geometry never moves or slopes are covered, everything else returns `None`.
+pub fn ground_heights(stage: StageID) -> Option<&'static [f32]> {
+    match stage {
+        StageID::Battlefield => Some(&[0.0, 27.2, 54.4]),
+        StageID::FinalDestination => Some(&[0.0]),
+        StageID::DreamLand => Some(&[0.0, 30.2422, 51.4253]),
+        _ => None,
+    }
+}
+
+/// Distance of each ledge from the center of the stage. Ledges are mirrored, so the left ledge is at `-x`.
+pub fn ledge_x(stage: StageID) -> Option<f32> {
+    match stage {
+        StageID::Battlefield => Some(68.4),
+        StageID::FinalDestination => Some(85.5657),
+        StageID::DreamLand => Some(77.2713),
+        StageID::PokemonStadium => Some(87.75),
+        _ => None,
+    }
+}
+
+/// True if `y` is within [`GROUND_TOLERANCE`] of one of the stage's surfaces. Always true for stages without
+/// known geometry.
+pub fn is_on_ground(stage: StageID, y: f32) -> bool {
+    ground_heights(stage).map_or(true, |heights| {
+        heights.iter().any(|h| (y - h).abs() <= GROUND_TOLERANCE)
+    })
+}
+
+impl Game {
+    /// Players flagged as grounded should be standing on an actual surface. A grounded player floating in
+    /// midair (or buried in the stage) usually means a corrupt frame or a tampered position.
+    pub(crate) fn validate_stage_geometry(&self) {
+        let stage = self.start.stage;
+        if ground_heights(stage).is_none() {
+            return;
+        }
+
+        for port in self.active_ports() {
+            let mut first = None;
+            let mut count = 0;
+            for frame in self.post_frames(port) {
+                if frame.is_grounded != Some(true)
+                    || is_death_state(frame.action_state)
+                    || is_on_ground(stage, frame.position.y)
+                {
+                    continue;
+                }
+                first.get_or_insert((frame.frame_index, frame.position.y));
+                count += 1;
+            }
+
+            if let Some((idx, y)) = first {
+                warn!(
+                    "[Frame {idx}, Port {port}] Grounded at y = {y}, which isn't a surface on {stage:?} ({count} frames total)"
+                );
+            }
+        }
+    }
+}
+
+impl Game {
+    /// Every Pokémon Stadium transformation, as `(frame index, form)`, in the order they happened. Each entry is
+    /// the first frame the stage reported the new form, so the initial transformation away from `Normal` and the
+    /// return to it both appear. Only replays from v3.18.0 record these, older replays return nothing.
+    pub fn stadium_transformations(&self) -> Vec<(i32, StadiumTransformation)> {
+        let mut result: Vec<(i32, StadiumTransformation)> = Vec::new();
+        for event in self
+            .raw_events
+            .iter()
+            .filter(|e| e.code == STADIUM_TRANSFORMATION_EVENT)
+        {
+            // frame index, transformation event, transformation type
+            let mut payload = event.payload.clone();
+            if payload.remaining() < 8 {
+                continue;
+            }
+            let frame_index = payload.get_i32();
+            payload.advance(2);
+            let Some(form) = StadiumTransformation::from_repr(payload.get_u16()) else {
+                continue;
+            };
+            // every step of a transformation reports the form, rollbacks report them again
+            if result.last().map_or(true, |(_, last)| *last != form) {
+                result.push((frame_index, form));
+            }
+        }
+
+        result
+    }
+
+    /// The height of Fountain of Dreams' side platforms on every frame they moved, in frame order with rollbacks
+    /// resolved. Only replays from v3.18.0 record these, older replays return nothing.
+    pub fn fod_platforms(&self) -> Vec<PlatformHeight> {
+        let mut heights = BTreeMap::new();
+        for event in self
+            .raw_events
+            .iter()
+            .filter(|e| e.code == FOD_PLATFORM_EVENT)
+        {
+            // frame index, platform, height
+            let mut payload = event.payload.clone();
+            if payload.remaining() < 9 {
+                continue;
+            }
+            let frame_index = payload.get_i32();
+            let Some(platform) = FodPlatform::from_repr(payload.get_u8()) else {
+                continue;
+            };
+            let height = payload.get_f32();
+            heights.insert(
+                (frame_index, platform as u8),
+                PlatformHeight {
+                    frame_index,
+                    platform,
+                    height,
+                },
+            );
+        }
+
+        heights.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, Bytes};
+
+    use super::*;
+    use crate::{game::RawEvent, parse::validate_bytes, Config};
+
+    fn with_events(events: impl IntoIterator<Item = (u8, Vec<u8>)>) -> Game {
+        let data = include_bytes!("../test_replays/non_broken.slp");
+        let mut game = validate_bytes(Bytes::from_static(data), &Config::default()).unwrap();
+        game.raw_events
+            .extend(events.into_iter().map(|(code, payload)| RawEvent {
+                code,
+                payload: Bytes::from(payload),
+            }));
+
+        game
+    }
+
+    fn transformation(frame_index: i32, event: u16, form: u16) -> (u8, Vec<u8>) {
+        let mut payload = Vec::new();
+        payload.put_i32(frame_index);
+        payload.put_u16(event);
+        payload.put_u16(form);
+        (STADIUM_TRANSFORMATION_EVENT, payload)
+    }
+
+    fn platform(frame_index: i32, platform: u8, height: f32) -> (u8, Vec<u8>) {
+        let mut payload = Vec::new();
+        payload.put_i32(frame_index);
+        payload.put_u8(platform);
+        payload.put_f32(height);
+        (FOD_PLATFORM_EVENT, payload)
+    }
+
+    #[test]
+    fn stadium_transformations() {
+        let game = with_events([
+            transformation(1200, 2, 3),
+            transformation(1260, 3, 3),
+            // re-simulated during a rollback
+            transformation(1258, 3, 3),
+            transformation(2400, 2, 5),
+            transformation(2460, 6, 5),
+            transformation(3600, 2, 6),
+        ]);
+
+        assert_eq!(
+            game.stadium_transformations(),
+            [
+                (1200, StadiumTransformation::Fire),
+                (2400, StadiumTransformation::Normal),
+                (3600, StadiumTransformation::Rock),
+            ]
+        );
+    }
+
+    #[test]
+    fn fod_platforms() {
+        let game = with_events([
+            platform(10, 1, 20.0),
+            platform(10, 0, 28.0),
+            platform(11, 1, 20.5),
+            // the rollback's re-simulation replaces the first
+            platform(11, 1, 21.0),
+        ]);
+
+        let heights: Vec<_> = game
+            .fod_platforms()
+            .iter()
+            .map(|p| (p.frame_index, p.platform, p.height))
+            .collect();
+        assert_eq!(
+            heights,
+            [
+                (10, FodPlatform::Right, 28.0),
+                (10, FodPlatform::Left, 20.0),
+                (11, FodPlatform::Left, 21.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use ssbm_utils::prelude::{Attack, Port};
+
+use crate::{
+    config::Config,
+    frame::FIRST_FRAME,
+    game::{Game, HitSource},
+    inputs::{is_tilted, ControllerState, PASSTHROUGH_BUTTONS},
+    postframe::PostFrame,
+    preframe::PreFrame,
+};
+
+/// Minimum length of an idle span, in frames (5 seconds)
+pub const IDLE_THRESHOLD: usize = 300;
+
+/// Horizontal distance, in units, within which two players are considered equally close to center stage. The
+/// higher player has control in that case.
+pub const CENTER_TOLERANCE: f32 = 5.0;
+
+/// Action states 0-10 are the blast zone deaths (bottom, sides, top, star KO, screen KO)
+pub fn is_death_state(action_state: u16) -> bool {
+    action_state <= 10
+}
+
+/// Tech in place, tech roll forward/backward, wall tech, wall jump tech, ceiling tech
+pub fn is_tech_state(action_state: u16) -> bool {
+    (199..=204).contains(&action_state)
+}
+
+/// Missed tech bounce, face up and face down
+pub fn is_missed_tech_state(action_state: u16) -> bool {
+    action_state == 183 || action_state == 191
+}
+
+/// Summary stats for a single port, see [`Game::compute_stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlayerStats {
+    /// Newly pressed buttons, plus sticks newly tilted past `Config::stick_deadzone`, per minute of in-game time
+    pub apm: f32,
+    /// Every change to the controller's state per minute of in-game time, see [`Game::ipm`]
+    pub ipm: f32,
+    /// added v2.0.0
+    pub l_cancels: u32,
+    /// added v2.0.0
+    pub l_cancel_misses: u32,
+    /// Percent dealt to opponents, as credited by their `last_hit_by`
+    pub damage_dealt: f32,
+    pub damage_received: f32,
+    /// Number of combos started, see [`Game::combos`]
+    pub openings: u32,
+    /// Openings that turned into more than a single hit, or into a kill
+    pub conversions: u32,
+    pub deaths: u32,
+    pub techs: u32,
+    pub missed_techs: u32,
+}
+
+impl PlayerStats {
+    /// `None` if the player never had an L-cancel opportunity
+    pub fn l_cancel_rate(&self) -> Option<f32> {
+        let total = self.l_cancels + self.l_cancel_misses;
+        (total > 0).then(|| self.l_cancels as f32 / total as f32)
+    }
+
+    /// Fraction (0.0-1.0) of openings that were converted, see [`crate::combo::Combo::is_conversion`]. `None`
+    /// if the player never got an opening.
+    pub fn conversion_rate(&self) -> Option<f32> {
+        (self.openings > 0).then(|| self.conversions as f32 / self.openings as f32)
+    }
+}
+
+impl ControllerState {
+    /// True if nothing on the controller is being pressed, and neither stick is tilted past `deadzone`
+    pub fn is_idle(&self, deadzone: f32) -> bool {
+        self.buttons == 0
+            && !is_tilted(&self.joystick, deadzone)
+            && !is_tilted(&self.cstick, deadzone)
+            && self.trigger_l == 0.0
+            && self.trigger_r == 0.0
+    }
+}
+
+impl Game {
+    /// Every stat in [`PlayerStats`], indexed by port. Each port's timeline is only walked once, so this is
+    /// much cheaper than computing the stats individually. Empty ports are left at their defaults.
+    pub fn compute_stats(&self, config: &Config) -> [PlayerStats; 4] {
+        let mut stats = [PlayerStats::default(); 4];
+
+        for combo in self.combos() {
+            stats[combo.attacker as usize].openings += 1;
+            if combo.is_conversion() {
+                stats[combo.attacker as usize].conversions += 1;
+            }
+        }
+
+        for port in self.active_ports() {
+            let frames = self.post_frames(port);
+            for w in frames.windows(2) {
+                let (prev, curr) = (w[0], w[1]);
+
+                let entered =
+                    |check: fn(u16) -> bool| !check(prev.action_state) && check(curr.action_state);
+                if entered(is_death_state) {
+                    stats[port as usize].deaths += 1;
+                }
+                if entered(is_tech_state) {
+                    stats[port as usize].techs += 1;
+                }
+                if entered(is_missed_tech_state) {
+                    stats[port as usize].missed_techs += 1;
+                }
+
+                match curr.l_cancel {
+                    Some(1) => stats[port as usize].l_cancels += 1,
+                    Some(2) => stats[port as usize].l_cancel_misses += 1,
+                    _ => (),
+                }
+
+                let damage = curr.percent - prev.percent;
+                if damage > 0.0 {
+                    stats[port as usize].damage_received += damage;
+                    if let Some(attacker) = curr.attacker() {
+                        stats[attacker as usize].damage_dealt += damage;
+                    }
+                }
+            }
+
+            let inputs = self.pre_frames(port);
+            let deadzone = config.stick_deadzone;
+            let presses: u32 = inputs
+                .windows(2)
+                .map(|w| {
+                    let (prev, curr) = (w[0], w[1]);
+                    let flicks = [
+                        (&prev.joystick, &curr.joystick),
+                        (&prev.cstick, &curr.cstick),
+                    ]
+                    .into_iter()
+                    .filter(|(p, c)| !is_tilted(p, deadzone) && is_tilted(c, deadzone))
+                    .count() as u32;
+                    (curr.controller_buttons & !prev.controller_buttons).count_ones() + flicks
+                })
+                .sum();
+            let minutes = minutes_played(&inputs);
+            if minutes > 0.0 {
+                stats[port as usize].apm = presses as f32 / minutes;
+                stats[port as usize].ipm = input_changes(&inputs) as f32 / minutes;
+            }
+        }
+
+        stats
+    }
+
+    /// Inputs per minute: every button press or release, plus every frame either stick or either analog
+    /// trigger moved at all, per minute of in-game time. Unlike APM nothing is filtered out, so this is
+    /// mostly a measure of how busy the controller was rather than of deliberate actions.
+    pub fn ipm(&self, port: Port) -> f32 {
+        let inputs = self.pre_frames(port);
+        let minutes = minutes_played(&inputs);
+        if minutes > 0.0 {
+            input_changes(&inputs) as f32 / minutes
+        } else {
+            0.0
+        }
+    }
+
+    /// Inclusive frame ranges of at least [`IDLE_THRESHOLD`] frames during which the given port had no input
+    /// at all (see [`ControllerState::is_idle`]). Long spans usually mean a disconnected controller or an AFK
+    /// player.
+    pub fn idle_spans(&self, port: Port, config: &Config) -> Vec<(i32, i32)> {
+        let mut spans = Vec::new();
+        let mut current: Option<(i32, i32)> = None;
+
+        for state in self.controller_states(port) {
+            if state.is_idle(config.stick_deadzone) {
+                current = match current {
+                    Some((start, _)) => Some((start, state.frame_index)),
+                    None => Some((state.frame_index, state.frame_index)),
+                };
+            } else if let Some(span) = current.take() {
+                spans.push(span);
+            }
+        }
+        spans.extend(current);
+
+        spans
+            .into_iter()
+            .filter(|(start, end)| (end - start + 1) as usize >= IDLE_THRESHOLD)
+            .collect()
+    }
+
+    /// Fraction of in-game frames (0.0-1.0) each port spent "in control" of the stage, indexed by port. A player
+    /// is in control when they're closer to center stage than their opponent, or when both are about as close
+    /// (see [`CENTER_TOLERANCE`]) and they're higher up. Only defined for games with exactly 2 active ports,
+    /// frames where either player is dead are skipped.
+    pub fn stage_control(&self) -> [Option<f32>; 4] {
+        let mut result = [None; 4];
+        let ports: Vec<Port> = self.active_ports().collect();
+        let [a, b] = ports[..] else {
+            return result;
+        };
+        let (ta, tb) = (self.post_frames_indexed(a), self.post_frames_indexed(b));
+
+        let mut control = [0usize; 2];
+        let mut total = 0;
+        for (fa, fb) in ta.iter().zip(tb.iter()) {
+            let (Some(fa), Some(fb)) = (fa, fb) else {
+                continue;
+            };
+            if fa.frame_index < 0
+                || is_death_state(fa.action_state)
+                || is_death_state(fb.action_state)
+            {
+                continue;
+            }
+            total += 1;
+
+            let (da, db) = (fa.position.x.abs(), fb.position.x.abs());
+            if (da - db).abs() <= CENTER_TOLERANCE {
+                if fa.position.y > fb.position.y {
+                    control[0] += 1;
+                } else if fb.position.y > fa.position.y {
+                    control[1] += 1;
+                }
+            } else if da < db {
+                control[0] += 1;
+            } else {
+                control[1] += 1;
+            }
+        }
+
+        if total > 0 {
+            result[a as usize] = Some(control[0] as f32 / total as f32);
+            result[b as usize] = Some(control[1] as f32 / total as f32);
+        }
+        result
+    }
+
+    /// Frames on which the physical buttons and the game's processed buttons disagree about any of the
+    /// [`PASSTHROUGH_BUTTONS`]. The processed buttons are derived from the physical ones, so on a healthy
+    /// replay this is empty. Frames before "GO!" are skipped since the game doesn't process input yet.
+    pub fn button_mismatches(&self, port: Port) -> Vec<i32> {
+        self.pre_frames(port)
+            .into_iter()
+            .filter(|f| f.frame_index >= 0)
+            .filter(|f| {
+                f.controller_buttons & PASSTHROUGH_BUTTONS
+                    != f.engine_buttons as u16 & PASSTHROUGH_BUTTONS
+            })
+            .map(|f| f.frame_index)
+            .collect()
+    }
+
+    /// Frames on which the given port entered a death state
+    pub fn deaths(&self, port: Port) -> Vec<i32> {
+        self.death_frames(port)
+            .into_iter()
+            .map(|f| f.frame_index)
+            .collect()
+    }
+
+    /// Deaths where nobody else was credited with the kill, i.e. `last_hit_by` is empty or the player themself
+    pub fn self_destructs(&self, port: Port) -> Vec<i32> {
+        self.death_frames(port)
+            .into_iter()
+            .filter(|f| f.attacker().is_none())
+            .map(|f| f.frame_index)
+            .collect()
+    }
+
+    /// Each opponent death credited to the given port (via the victim's `last_hit_by`), along with the move
+    /// that caused it. When the victim's `instance_hit_by` (v3.16.0+) resolves to the killer's own frame, the
+    /// move is read from there, otherwise from the killer's frame at the moment of death.
+    pub fn kill_moves(&self, port: Port) -> Vec<(i32, Attack)> {
+        let killer = self.post_frames_indexed(port);
+        let mut kills = Vec::new();
+
+        for victim in self.active_ports().filter(|p| *p != port) {
+            for death in self.death_frames(victim) {
+                if death.attacker() != Some(port) {
+                    continue;
+                }
+                let attack = match self.hit_source(death) {
+                    Some(HitSource::Player(f)) if f.port == port as u8 => f.last_attack(),
+                    _ => usize::try_from(death.frame_index as i64 - FIRST_FRAME as i64)
+                        .ok()
+                        .and_then(|i| killer.get(i).copied().flatten())
+                        .and_then(|f| f.last_attack()),
+                };
+                if let Some(attack) = attack {
+                    kills.push((death.frame_index, attack));
+                }
+            }
+        }
+        kills.sort_by_key(|(idx, _)| *idx);
+
+        kills
+    }
+
+    /// The first frame of each death
+    fn death_frames(&self, port: Port) -> Vec<&PostFrame> {
+        self.post_frames(port)
+            .windows(2)
+            .filter(|w| !is_death_state(w[0].action_state) && is_death_state(w[1].action_state))
+            .map(|w| w[1])
+            .collect()
+    }
+}
+
+/// In-game time covered by the frames, frames before "GO!" aren't counted
+fn minutes_played(inputs: &[&PreFrame]) -> f32 {
+    inputs.iter().filter(|f| f.frame_index >= 0).count() as f32 / 3600.0
+}
+
+/// Buttons pressed or released, plus one per frame that each stick and each analog trigger changed
+fn input_changes(inputs: &[&PreFrame]) -> u32 {
+    inputs
+        .windows(2)
+        .map(|w| {
+            let (prev, curr) = (w[0], w[1]);
+            let analog = [
+                prev.joystick != curr.joystick,
+                prev.cstick != curr.cstick,
+                prev.controller_l != curr.controller_l,
+                prev.controller_r != curr.controller_r,
+            ]
+            .into_iter()
+            .filter(|changed| *changed)
+            .count() as u32;
+            (curr.controller_buttons ^ prev.controller_buttons).count_ones() + analog
+        })
+        .sum()
+}
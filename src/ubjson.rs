@@ -1,6 +1,6 @@
-use std::io::{Error, ErrorKind, Read, Result};
+use std::io::{Error, ErrorKind, Read, Result, Write};
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use serde_json::{Map, Value};
 
 fn to_utf8<R: Read>(r: &mut R) -> Result<String> {
@@ -10,7 +10,10 @@ fn to_utf8<R: Read>(r: &mut R) -> Result<String> {
     String::from_utf8(buf).map_err(|e| Error::new(ErrorKind::InvalidData, e))
 }
 
-fn to_val<R: Read>(r: &mut R) -> Result<Value> {
+/// Nested objects deeper than this are rejected rather than risking a stack overflow on malformed input
+const MAX_DEPTH: usize = 32;
+
+fn to_val<R: Read>(r: &mut R, depth: usize) -> Result<Value> {
     match r.read_u8()? {
         // "S": str
         0x53 => match r.read_u8()? {
@@ -25,7 +28,7 @@ fn to_val<R: Read>(r: &mut R) -> Result<Value> {
             r.read_i32::<BigEndian>()?,
         ))),
         // "{": map
-        0x7b => Ok(Value::Object(to_map(r)?)),
+        0x7b => Ok(Value::Object(to_map_inner(r, depth + 1)?)),
         c => Err(Error::new(
             ErrorKind::InvalidData,
             format!("unexpected UBJSON value type: {}", c),
@@ -45,13 +48,71 @@ fn to_key<R: Read>(r: &mut R) -> Result<Option<String>> {
 }
 
 pub(crate) fn to_map<R: Read>(r: &mut R) -> Result<Map<String, Value>> {
+    to_map_inner(r, 0)
+}
+
+fn to_map_inner<R: Read>(r: &mut R, depth: usize) -> Result<Map<String, Value>> {
+    if depth > MAX_DEPTH {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "UBJSON objects nested too deeply",
+        ));
+    }
     let mut m = Map::new();
     while match to_key(r)? {
         Some(k) => {
-            m.insert(k, to_val(r)?);
+            m.insert(k, to_val(r, depth)?);
             true
         }
         None => false,
     } {}
     Ok(m)
 }
+
+fn write_utf8<W: Write>(w: &mut W, s: &str) -> Result<()> {
+    let length = u8::try_from(s.len()).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("UBJSON string too long: {}", s.len()),
+        )
+    })?;
+    w.write_u8(length)?;
+    w.write_all(s.as_bytes())
+}
+
+fn write_val<W: Write>(w: &mut W, v: &Value) -> Result<()> {
+    match v {
+        Value::String(s) => {
+            w.write_all(&[0x53, 0x55])?;
+            write_utf8(w, s)
+        }
+        Value::Number(n) => match n.as_i64().and_then(|n| i32::try_from(n).ok()) {
+            Some(n) => {
+                w.write_u8(0x6c)?;
+                w.write_i32::<BigEndian>(n)
+            }
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unsupported UBJSON number: {}", n),
+            )),
+        },
+        Value::Object(m) => {
+            w.write_u8(0x7b)?;
+            write_map(w, m)
+        }
+        v => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("unsupported UBJSON value: {}", v),
+        )),
+    }
+}
+
+/// Inverse of `to_map`. Writes the map's entries followed by the closing `}`, but not the opening `{`.
+pub(crate) fn write_map<W: Write>(w: &mut W, m: &Map<String, Value>) -> Result<()> {
+    for (k, v) in m {
+        w.write_u8(0x55)?;
+        write_utf8(w, k)?;
+        write_val(w, v)?;
+    }
+    w.write_u8(0x7d)
+}
@@ -1,5 +1,8 @@
 use std::fmt::Display;
 
+use anyhow::{ensure, Result};
+use bytes::{Buf, Bytes};
+use ssbm_utils::enums::character::Character;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -10,13 +13,77 @@ pub enum ParseError {
     PlayerCount,
     #[error("Expected {0}, got {1}")]
     Value(String, String),
+    /// The file does not start with a Slippi header, so it is not a replay at all
+    #[error("Not a Slippi replay: {0}")]
+    NotSlp(String),
+    /// The file is a Slippi replay, but its contents are malformed starting at `offset`
+    #[error("Corrupt replay at file pos {offset}: {context}")]
+    CorruptStream { offset: usize, context: String },
+    /// The game's settings are not tournament legal. Only raised in strict mode
+    #[error("Illegal game settings: {0}")]
+    Illegal(&'static str),
+    /// The parser panicked. Only returned when panics are being caught, see `Config::catch_panics`
+    #[error("Parser panicked: {0}")]
+    Panic(String),
+    /// An error was reported with `Config::fail_fast` set, parsing stopped there
+    #[error("Validation failed: {0}")]
+    Validation(String),
 }
 
 
+impl ParseError {
+    /// Position in the file where parsing failed, if known. Errors returned by the parser can be recovered with
+    /// `anyhow::Error::downcast_ref::<ParseError>()`.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            ParseError::CorruptStream { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
+/// Errors if `stream` doesn't contain at least `needed` bytes, so the fixed-size reads that follow can't
+/// panic.
+pub(crate) fn ensure_len(stream: &Bytes, needed: usize, event: &str) -> Result<()> {
+    ensure!(
+        stream.remaining() >= needed,
+        ParseError::Value(
+            format!("at least {needed} bytes for {event}"),
+            format!("{} bytes", stream.remaining())
+        )
+    );
+    Ok(())
+}
+
 pub trait Tournament {
     fn is_legal(&self) -> bool;
 }
 
+/// Result of a ruleset check that can fail for a specific reason
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Legality {
+    Legal,
+    Illegal(&'static str),
+    /// The replay predates the field required to make the check
+    Undetermined(&'static str),
+}
+
+/// Returns true if the orientation is within `epsilon` of one of the valid values (-1, 0, 1)
+pub fn is_valid_orientation(orientation: f32, epsilon: f32) -> bool {
+    [-1.0, 0.0, 1.0]
+        .iter()
+        .any(|o| (orientation - o).abs() <= epsilon)
+}
+
+/// Total number of jumps (grounded jump included) a character has when fully refreshed. Kirby and
+/// Jigglypuff get 5 midair jumps, everyone else gets 1.
+pub fn max_jumps(character: Character) -> u8 {
+    match character {
+        Character::Kirby | Character::Jigglypuff => 6,
+        _ => 2,
+    }
+}
+
 /// Slippi replay version, dictates what information is available in the replay.
 ///
 /// Version release dates listed below. Note that date checks can be misleading due to incorrectly
@@ -39,6 +106,7 @@ pub trait Tournament {
 /// | 2.1.0   | Apr 28 2019 |
 /// | 2.2.0   | Jun 24 2019 |
 /// | 3.0.0   | Oct 24 2019 |
+/// | 3.1.0   | Unknown     |
 /// | 3.2.0   | Jan 31 2020 |
 /// | 3.3.0   | Feb 11 2020 |
 /// | 3.5.0   | Jun 13 2020 |
@@ -64,6 +132,7 @@ pub trait Tournament {
 /// * Ranked was released Dec 12 2022
 ///
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct Version {
     pub major: u8,
     pub minor: u8,
@@ -72,7 +141,7 @@ pub struct Version {
 
 impl Version {
     #[inline]
-    pub fn new(major: u8, minor: u8, build: u8) -> Self {
+    pub const fn new(major: u8, minor: u8, build: u8) -> Self {
         Self {
             major,
             minor,
@@ -95,10 +164,75 @@ impl Version {
     pub fn as_u32(&self) -> u32 {
         u32::from_be_bytes([self.major, self.minor, self.build, 0])
     }
+
+    /// Inverse of [`Version::as_u32`]. The lowest byte is unused and ignored.
+    #[inline]
+    pub const fn from_u32(version: u32) -> Self {
+        let [major, minor, build, _] = version.to_be_bytes();
+        Self::new(major, minor, build)
+    }
+
+    /// Major, minor, build, in the order they're stored in the replay's GameStart event
+    #[inline]
+    pub const fn from_bytes(bytes: [u8; 3]) -> Self {
+        Self::new(bytes[0], bytes[1], bytes[2])
+    }
 }
 
+/// Every release in the table above, oldest first
+pub const SPEC_VERSIONS: [Version; 26] = [
+    Version::new(0, 1, 0),
+    Version::new(0, 2, 0),
+    Version::new(1, 0, 0),
+    Version::new(1, 2, 0),
+    Version::new(1, 3, 0),
+    Version::new(1, 4, 0),
+    Version::new(1, 5, 0),
+    Version::new(2, 0, 0),
+    Version::new(2, 1, 0),
+    Version::new(2, 2, 0),
+    Version::new(3, 0, 0),
+    Version::new(3, 1, 0),
+    Version::new(3, 2, 0),
+    Version::new(3, 3, 0),
+    Version::new(3, 5, 0),
+    Version::new(3, 6, 0),
+    Version::new(3, 7, 0),
+    Version::new(3, 8, 0),
+    Version::new(3, 9, 0),
+    Version::new(3, 10, 0),
+    Version::new(3, 11, 0),
+    Version::new(3, 12, 0),
+    Version::new(3, 13, 0),
+    Version::new(3, 14, 0),
+    Version::new(3, 15, 0),
+    Version::new(3, 16, 0),
+];
+
 impl Display for Version {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "v{}.{}.{}", self.major, self.minor, self.build)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_round_trips_through_u32() {
+        for version in SPEC_VERSIONS.into_iter().chain([Version::new(255, 254, 253)]) {
+            assert_eq!(Version::from_u32(version.as_u32()), version);
+        }
+        // the lowest byte isn't part of the version
+        assert_eq!(Version::from_u32(0x030F_00FF), Version::new(3, 15, 0));
+    }
+
+    #[test]
+    fn version_from_bytes() {
+        assert_eq!(Version::from_bytes([3, 16, 0]), Version::new(3, 16, 0));
+        assert_eq!(
+            Version::from_bytes([1, 2, 3]).as_u32(),
+            u32::from_be_bytes([1, 2, 3, 0])
+        );
+    }
+}
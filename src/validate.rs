@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+
+use ssbm_utils::prelude::Port;
+
+use crate::{
+    config::Config,
+    frame::FIRST_PLAYABLE_FRAME,
+    game::Game,
+    postframe::{flags, hurtbox, PostFrame},
+    sink::warn,
+};
+
+impl Game {
+    /// Checks that span multiple frames, and thus need the finalized timeline rather than a single event
+    pub(crate) fn validate(&self, config: &Config) {
+        let timelines: Vec<_> = self
+            .active_ports()
+            .map(|port| (port, self.post_frames_indexed(port)))
+            .collect();
+
+        for port in self.active_ports() {
+            let frames = self.post_frames(port);
+            self.validate_starting_stocks(port, &frames);
+            self.validate_pre_go(port, &frames);
+            self.validate_state_frames(port, &frames);
+            self.validate_buttons(port);
+            self.validate_raw_sticks(port, config.stick_deadzone);
+            self.validate_velocities(port, &frames);
+            self.validate_hurtbox(port, &frames);
+        }
+
+        self.validate_combo_counts(&timelines);
+        self.validate_instance_ids();
+        self.validate_frame_counter();
+        self.validate_stage_geometry();
+    }
+
+    /// The first frame's stock count should match `Player::starting_stocks` from GameStart. A mismatch usually
+    /// means the replay was spliced together from more than one game.
+    fn validate_starting_stocks(&self, port: Port, frames: &[&PostFrame]) {
+        let Some(first) = frames.first() else {
+            return;
+        };
+        let expected = self.players[port as usize].starting_stocks;
+        if first.stocks != expected {
+            warn!(
+                "[Frame {}, Port {port}] Starts with {} stocks, GameStart says {}",
+                first.frame_index, first.stocks, expected
+            );
+        }
+    }
+
+    /// Before [`FIRST_PLAYABLE_FRAME`] players can't do anything, they should stay in their entry animation (or
+    /// standing) without moving. Anything else means the start of the replay was tampered with or corrupted.
+    fn validate_pre_go(&self, port: Port, frames: &[&PostFrame]) {
+        // Wait, Entry, EntryStart, EntryEnd
+        const PRE_GO_STATES: [u16; 4] = [14, 322, 323, 324];
+        const POSITION_EPSILON: f32 = 0.01;
+
+        let pre_go: Vec<_> = frames
+            .iter()
+            .take_while(|f| f.frame_index < FIRST_PLAYABLE_FRAME)
+            .collect();
+
+        if let Some(f) = pre_go
+            .iter()
+            .find(|f| !PRE_GO_STATES.contains(&f.action_state))
+        {
+            warn!(
+                "[Frame {}, Port {port}] In action state {} before players can act",
+                f.frame_index, f.action_state
+            );
+        }
+
+        if let Some(w) = pre_go.windows(2).find(|w| {
+            (w[1].position.x - w[0].position.x).abs() > POSITION_EPSILON
+                || (w[1].position.y - w[0].position.y).abs() > POSITION_EPSILON
+        }) {
+            warn!(
+                "[Frame {}, Port {port}] Moved before players can act: ({}, {}) -> ({}, {})",
+                w[1].frame_index,
+                w[0].position.x,
+                w[0].position.y,
+                w[1].position.x,
+                w[1].position.y
+            );
+        }
+    }
+
+    /// Grounded players move with their ground velocity and airborne players with their air velocity, the other
+    /// one should be zeroed. Frames where the player just landed or left the ground are skipped, the velocity
+    /// is carried over on those. The y speed is shared between both, so only x is checked.
+    fn validate_velocities(&self, port: Port, frames: &[&PostFrame]) {
+        const VELOCITY_EPSILON: f32 = 0.01;
+
+        let mut first = None;
+        let mut count = 0;
+        for w in frames.windows(2) {
+            let (prev, curr) = (w[0], w[1]);
+            let (Some(grounded), Some(air), Some(ground)) =
+                (curr.is_grounded, &curr.air_velocity, &curr.ground_velocity)
+            else {
+                continue;
+            };
+            if prev.is_grounded != Some(grounded) {
+                continue;
+            }
+
+            let stray = if grounded { air.x } else { ground.x };
+            if stray.abs() > VELOCITY_EPSILON {
+                first.get_or_insert((curr.frame_index, grounded, stray));
+                count += 1;
+            }
+        }
+
+        if let Some((idx, grounded, stray)) = first {
+            let (state, kind) = if grounded {
+                ("Grounded", "air")
+            } else {
+                ("Airborne", "ground")
+            };
+            warn!("[Frame {idx}, Port {port}] {state} player has {kind} x velocity {stray} ({count} frames total)");
+        }
+    }
+
+    /// `hurtbox_state` (v3.1.0+) should line up with the states that grant invulnerability. Players can't be hit
+    /// while respawning, and every dodge is intangible for part of its animation. The exact intangible frames
+    /// differ per character, so dodges are only checked as a whole.
+    fn validate_hurtbox(&self, port: Port, frames: &[&PostFrame]) {
+        // Rebirth, RebirthWait
+        const RESPAWN: [u16; 2] = [12, 13];
+        // EscapeF, EscapeB, Escape (spotdodge), EscapeAir
+        const DODGES: [u16; 4] = [233, 234, 235, 236];
+        // every dodge's intangibility has started by this many frames in
+        const MIN_DODGE_FRAMES: usize = 20;
+
+        let mut first_respawn = None;
+        let mut respawn_count = 0;
+        let mut dodge_start: Option<usize> = None;
+
+        for (i, frame) in frames.iter().enumerate() {
+            let Some(state) = frame.hurtbox_state else {
+                return;
+            };
+            if RESPAWN.contains(&frame.action_state) && state == hurtbox::VULNERABLE {
+                first_respawn.get_or_insert(frame.frame_index);
+                respawn_count += 1;
+            }
+
+            let in_dodge = DODGES.contains(&frame.action_state);
+            let continues =
+                dodge_start.is_some_and(|s| frames[s].action_state == frame.action_state);
+            if continues {
+                continue;
+            }
+            if let Some(start) = dodge_start.take() {
+                let dodge = &frames[start..i];
+                if dodge.len() >= MIN_DODGE_FRAMES
+                    && dodge
+                        .iter()
+                        .all(|f| f.hurtbox_state == Some(hurtbox::VULNERABLE))
+                {
+                    warn!(
+                        "[Frame {}, Port {port}] Vulnerable for all {} frames of dodge state {}",
+                        dodge[0].frame_index,
+                        dodge.len(),
+                        dodge[0].action_state
+                    );
+                }
+            }
+            if in_dodge {
+                dodge_start = Some(i);
+            }
+        }
+
+        if let Some(idx) = first_respawn {
+            warn!("[Frame {idx}, Port {port}] Vulnerable while respawning ({respawn_count} frames total)");
+        }
+    }
+
+    /// A handful of mismatched frames can be put down to odd edge cases, but a consistent divergence between the
+    /// physical and processed buttons means something other than the controller is feeding the game inputs.
+    fn validate_buttons(&self, port: Port) {
+        // fraction of in-game frames that may mismatch before it's considered systematic
+        const MAX_MISMATCH_RATIO: f32 = 0.01;
+
+        let mismatches = self.button_mismatches(port);
+        let Some(first) = mismatches.first() else {
+            return;
+        };
+        let total = self
+            .pre_frames(port)
+            .iter()
+            .filter(|f| f.frame_index >= 0)
+            .count();
+        let ratio = mismatches.len() as f32 / total as f32;
+
+        if ratio > MAX_MISMATCH_RATIO {
+            warn!(
+                "[Port {port}] Physical and processed buttons disagree on {} of {total} frames ({:.1}%), first on frame {first}",
+                mismatches.len(),
+                ratio * 100.0
+            );
+        }
+    }
+
+    /// The raw stick position (both axes from v3.15.0) is the hardware value before the game applies its
+    /// deadzone and scaling, so the processed joystick should follow from it. UCF adjusts a few frames here and
+    /// there, but a consistent disagreement means something other than the controller is setting the processed
+    /// values.
+    fn validate_raw_sticks(&self, port: Port, deadzone: f32) {
+        // higher than the 1% allowed for buttons: UCF's dashback and shield drop fixes rewrite the processed stick
+        // on the frames they apply to, which adds up to a few percent for players who dashdance a lot
+        const MAX_STICK_MISMATCH_RATIO: f32 = 0.05;
+
+        let mut first = None;
+        let mut mismatches = 0;
+        let mut total = 0;
+        for frame in self.pre_frames(port) {
+            let (Some(x), Some(y)) = (frame.raw_stick_x, frame.raw_stick_y) else {
+                continue;
+            };
+            let raw = [x as f32 / RAW_STICK_SCALE, y as f32 / RAW_STICK_SCALE];
+            // past the edge of the gate the game clamps the position to the unit circle
+            if frame.frame_index < 0 || raw[0].hypot(raw[1]) > 1.0 {
+                continue;
+            }
+
+            total += 1;
+            if !raw_axis_matches(raw[0], frame.joystick.x, deadzone)
+                || !raw_axis_matches(raw[1], frame.joystick.y, deadzone)
+            {
+                first.get_or_insert(frame.frame_index);
+                mismatches += 1;
+            }
+        }
+
+        let Some(first) = first else {
+            return;
+        };
+        let ratio = mismatches as f32 / total as f32;
+        if ratio > MAX_STICK_MISMATCH_RATIO {
+            warn!(
+                "[Port {port}] Raw and processed stick positions disagree on {mismatches} of {total} frames ({:.1}%), first on frame {first}",
+                ratio * 100.0
+            );
+        }
+    }
+
+    /// `FrameStart::frame_counter` (v3.10.0+) should advance by exactly 1 whenever the frame index advances by 1,
+    /// and should only ever go backwards alongside the frame index during a rollback.
+    fn validate_frame_counter(&self) {
+        for w in self.frame_starts.windows(2) {
+            let (prev, curr) = (&w[0], &w[1]);
+            let (Some(prev_fc), Some(curr_fc)) = (prev.frame_counter, curr.frame_counter) else {
+                continue;
+            };
+            let idx = curr.frame_idx;
+
+            if curr.frame_idx < prev.frame_idx {
+                // rollback, the counter is re-simulated along with the frame
+                continue;
+            }
+            if curr_fc < prev_fc {
+                warn!("[Frame {idx}] Frame counter went backwards: {prev_fc} -> {curr_fc}");
+            } else if curr.frame_idx as i64 == prev.frame_idx as i64 + 1
+                && curr_fc != prev_fc.wrapping_add(1)
+            {
+                warn!("[Frame {idx}] Frame counter skipped from {prev_fc} to {curr_fc}");
+            }
+        }
+    }
+
+    /// Every `instance_hit_by` should refer to an `instance_id` carried by some player or item in the game
+    fn validate_instance_ids(&self) {
+        let known: HashSet<u16> = self
+            .post_frames
+            .iter()
+            .filter_map(|f| f.instance_id)
+            .chain(self.item_frames.iter().filter_map(|f| f.instance_id))
+            .collect();
+        // instance_hit_by persists across frames, only report each unknown ID once
+        let mut reported = HashSet::new();
+
+        for frame in &self.post_frames {
+            let Some(id) = frame.instance_hit_by else {
+                continue;
+            };
+            if id != 0 && !known.contains(&id) && reported.insert(id) {
+                warn!(
+                    "[Frame {}, Port{}] Hit by unknown instance ID: {id}",
+                    frame.frame_index, frame.port
+                );
+            }
+        }
+    }
+
+    /// The state frame counter should increase steadily within a state. It can go back to the start of the
+    /// animation (looping animations, re-entering the same state) but should never jump backwards to the middle
+    /// of the animation.
+    fn validate_state_frames(&self, port: Port, frames: &[&PostFrame]) {
+        // 1 minute of game time, far beyond the length of any non-looping animation
+        const MAX_STATE_FRAME: f32 = 3600.0;
+
+        for w in frames.windows(2) {
+            let (prev, curr) = (w[0], w[1]);
+            let (Some(prev_sf), Some(curr_sf)) = (prev.state_frame, curr.state_frame) else {
+                continue;
+            };
+            let idx = curr.frame_index;
+
+            if curr.action_state == prev.action_state && curr_sf < prev_sf && curr_sf > 1.0 {
+                warn!(
+                    "[Frame {idx}, Port {port}] State frame went backwards within state {}: {prev_sf} -> {curr_sf}",
+                    curr.action_state
+                );
+            }
+            if curr_sf > MAX_STATE_FRAME {
+                warn!("[Frame {idx}, Port {port}] Unreasonably large state frame: {curr_sf}");
+            }
+        }
+    }
+
+    /// Combo counts should only ever go up one hit at a time, and should drop back to 0 once nobody is in
+    /// hitstun anymore.
+    fn validate_combo_counts(&self, timelines: &[(Port, Vec<Option<&PostFrame>>)]) {
+        // generous upper bound on how long a combo count can linger after the last hitstun ends
+        const RESET_WINDOW: u32 = 120;
+
+        let len = timelines.iter().map(|(_, t)| t.len()).max().unwrap_or(0);
+        let mut since_hitstun = 0;
+        let mut prev: Vec<Option<&PostFrame>> = vec![None; timelines.len()];
+        let mut warned = vec![false; timelines.len()];
+
+        for i in 0..len {
+            let any_hitstun = timelines.iter().any(|(_, t)| {
+                t.get(i)
+                    .copied()
+                    .flatten()
+                    .is_some_and(|f| f.has_flag(flags::HITSTUN | flags::HITLAG))
+            });
+            since_hitstun = if any_hitstun { 0 } else { since_hitstun + 1 };
+
+            for (j, (port, timeline)) in timelines.iter().enumerate() {
+                let Some(curr) = timeline.get(i).copied().flatten() else {
+                    continue;
+                };
+                let idx = curr.frame_index;
+
+                if let Some(p) = prev[j] {
+                    if curr.combo_count > p.combo_count.saturating_add(1) {
+                        warn!(
+                            "[Frame {idx}, Port {port}] Combo count jumped from {} to {}",
+                            p.combo_count, curr.combo_count
+                        );
+                    }
+                }
+
+                if curr.combo_count == 0 {
+                    warned[j] = false;
+                } else if curr.flags.is_some() && since_hitstun > RESET_WINDOW && !warned[j] {
+                    warned[j] = true;
+                    warn!(
+                        "[Frame {idx}, Port {port}] Combo count {} not reset {since_hitstun} frames after hitstun ended",
+                        curr.combo_count
+                    );
+                }
+
+                prev[j] = Some(curr);
+            }
+        }
+    }
+}
+
+/// Raw stick values are in hardware units, this many per 1.0 of processed stick
+const RAW_STICK_SCALE: f32 = 80.0;
+
+/// True if the processed axis value is what the game would produce from the raw one. Values within a unit of the
+/// deadzone's edge could go either way and always match.
+fn raw_axis_matches(raw: f32, processed: f32, deadzone: f32) -> bool {
+    const TOLERANCE: f32 = 1.0 / RAW_STICK_SCALE;
+
+    if (raw.abs() - deadzone).abs() <= TOLERANCE {
+        return true;
+    }
+    let expected = if raw.abs() <= deadzone { 0.0 } else { raw };
+
+    (processed - expected).abs() <= TOLERANCE
+}